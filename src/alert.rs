@@ -0,0 +1,204 @@
+// src/alert.rs
+use crate::config::TargetConfig;
+use crate::db::Db;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Duration, Utc};
+use log::{debug, error, info};
+use std::str::FromStr;
+use teloxide::prelude::Requester;
+use teloxide::types::ChatId;
+use teloxide::Bot;
+
+/// 静音 / 取消静音时表示全部目标的别名
+pub const ALL_TARGETS: &str = "*";
+
+/// 告警级别，每个会话可以选择接收哪些级别
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// 全部丢包
+    Down,
+    /// 部分丢包、延迟超限或 DNS 解析变化
+    Degraded,
+    /// 恢复
+    Recovered,
+    /// 证书即将过期
+    Cert,
+}
+
+impl Severity {
+    pub const ALL: [Severity; 4] = [
+        Severity::Down,
+        Severity::Degraded,
+        Severity::Recovered,
+        Severity::Cert,
+    ];
+
+    /// 命令与数据库中使用的名称
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Severity::Down => "down",
+            Severity::Degraded => "degraded",
+            Severity::Recovered => "recovered",
+            Severity::Cert => "cert",
+        }
+    }
+
+    /// 中文描述
+    pub fn label(self) -> &'static str {
+        match self {
+            Severity::Down => "离线",
+            Severity::Degraded => "性能下降",
+            Severity::Recovered => "恢复",
+            Severity::Cert => "证书到期",
+        }
+    }
+}
+
+impl FromStr for Severity {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Severity::ALL
+            .into_iter()
+            .find(|sev| sev.as_str().eq_ignore_ascii_case(s))
+            .ok_or_else(|| anyhow!("未知告警级别: {}（可选 down/degraded/recovered/cert）", s))
+    }
+}
+
+/// 会话的告警偏好
+#[derive(Debug, Clone, Default)]
+pub struct ChatSettings {
+    /// 接收的告警级别，`None` 表示全部
+    pub severities: Option<Vec<Severity>>,
+    /// 尚未到期的静音：(别名或 `ALL_TARGETS`, 截止时间)
+    pub mutes: Vec<(String, DateTime<Utc>)>,
+}
+
+impl ChatSettings {
+    /// 是否接收该级别的告警
+    pub fn accepts(&self, severity: Severity) -> bool {
+        self.severities
+            .as_ref()
+            .is_none_or(|list| list.contains(&severity))
+    }
+
+    /// 该目标此刻是否被静音
+    pub fn is_muted(&self, alias: &str, now: DateTime<Utc>) -> bool {
+        self.mutes
+            .iter()
+            .any(|(a, until)| (a == alias || a == ALL_TARGETS) && *until > now)
+    }
+}
+
+/// 把某个目标的告警消息推送给所有能看到该目标的订阅会话
+///
+/// 未选择该级别的会话直接跳过；静音中的会话不推送，但会记入告警日志，
+/// 之后可以用 `/missed` 查看。
+pub async fn broadcast(
+    bot: &Bot,
+    db: &Db,
+    target: &TargetConfig,
+    severity: Severity,
+    text: &str,
+) {
+    let chats = match db.list_subscriptions().await {
+        Ok(c) => c,
+        Err(e) => {
+            error!("读取订阅列表失败: {}", e);
+            return;
+        }
+    };
+    let assigned = match db.target_chats(&target.alias).await {
+        Ok(c) => c,
+        Err(e) => {
+            error!("读取目标归属失败 [{}]: {}", target.alias, e);
+            return;
+        }
+    };
+    let chats: Vec<i64> = chats
+        .into_iter()
+        .filter(|&chat_id| target.visible_to(chat_id, &assigned))
+        .collect();
+    info!("推送告警到 {} 个会话: {}", chats.len(), text);
+    let now = Utc::now();
+    for chat_id in chats {
+        let settings = match db.chat_settings(chat_id).await {
+            Ok(s) => s,
+            Err(e) => {
+                error!("读取会话设置失败 [{}]: {}", chat_id, e);
+                ChatSettings::default()
+            }
+        };
+        if !settings.accepts(severity) {
+            continue;
+        }
+        let muted = settings.is_muted(&target.alias, now);
+        if muted {
+            debug!("[{}] 会话 {} 已静音，仅记录告警", target.alias, chat_id);
+        } else if let Err(e) = bot.send_message(ChatId(chat_id), text).await {
+            error!("推送告警失败 [{}]: {}", chat_id, e);
+        }
+        if let Err(e) = db
+            .log_alert(chat_id, &target.alias, severity, text, now, !muted)
+            .await
+        {
+            error!("写入告警日志失败 [{}]: {}", chat_id, e);
+        }
+    }
+}
+
+/// 解析 “30m”、“2h”、“1d12h” 形式的时长，单位支持 s/m/h/d/w
+pub fn parse_duration(s: &str) -> Result<Duration> {
+    let mut total = Duration::zero();
+    let mut num = String::new();
+    for c in s.trim().chars() {
+        if c.is_ascii_digit() {
+            num.push(c);
+            continue;
+        }
+        let n: i64 = num
+            .parse()
+            .map_err(|_| anyhow!("无效时长: {}（例如 30m、2h、1d）", s))?;
+        num.clear();
+        let part = match c.to_ascii_lowercase() {
+            's' => Duration::try_seconds(n),
+            'm' => Duration::try_minutes(n),
+            'h' => Duration::try_hours(n),
+            'd' => Duration::try_days(n),
+            'w' => Duration::try_weeks(n),
+            _ => return Err(anyhow!("无效时长单位 {}: {}", c, s)),
+        };
+        total = part
+            .and_then(|d| total.checked_add(&d))
+            .ok_or_else(|| anyhow!("时长过长: {}", s))?;
+    }
+    if !num.is_empty() || total <= Duration::zero() {
+        return Err(anyhow!("无效时长: {}（例如 30m、2h、1d）", s));
+    }
+    Ok(total)
+}
+
+/// 把时长格式化为 “1天2小时3分4秒” 形式
+pub fn format_duration(d: Duration) -> String {
+    let secs = d.num_seconds().max(0);
+    let (days, hours, mins, secs) = (
+        secs / 86400,
+        secs % 86400 / 3600,
+        secs % 3600 / 60,
+        secs % 60,
+    );
+    let mut s = String::new();
+    if days > 0 {
+        s.push_str(&format!("{}天", days));
+    }
+    if hours > 0 {
+        s.push_str(&format!("{}小时", hours));
+    }
+    if mins > 0 {
+        s.push_str(&format!("{}分", mins));
+    }
+    if secs > 0 || s.is_empty() {
+        s.push_str(&format!("{}秒", secs));
+    }
+    s
+}
@@ -1,15 +1,18 @@
 // src/cmd.rs
 
 //! Central command dispatcher
-use crate::commands::{graph, isonline, start, stop, uptime};
-use crate::config::Config;
+use crate::commands::{
+    add, alerts, assign, certs, dashboard, export, graph, incidents, isonline, maintenance, missed, mute, remove, sla, start,
+    stop, targets, uptime,
+};
+use crate::config::{Config, TargetConfig};
 use crate::db::Db;
-use std::net::SocketAddr;
+use crate::targets::Targets;
 use std::sync::Arc;
-use futures::TryFutureExt;
-use teloxide::types::ChatKind;
+use teloxide::types::{ChatId, ChatKind};
 use teloxide::Bot;
 use teloxide::{dptree, macros::BotCommands, prelude::*};
+use tokio::sync::Semaphore;
 
 #[derive(BotCommands, Clone)]
 #[command(rename_rule = "lowercase", description = "这些命令可用:")]
@@ -20,27 +23,63 @@ enum Command {
     Stop,
     #[command(description = "检查在线状态")]
     Isonline,
-    #[command(description = "获取延迟曲线")]
-    Graph,
-    #[command(description = "简单获取前2小时在线状态")]
-    Uptime,
+    #[command(description = "获取曲线：/graph [别名...] [range=1h] [metric=latency|loss|p95|min|max]")]
+    Graph(String),
+    #[command(description = "在线状态：/uptime [时间跨度=1h] [每格时长=15m]")]
+    Uptime(String),
+    #[command(description = "延迟与丢包总览图：/dashboard [时间跨度=6h]")]
+    Dashboard(String),
+    #[command(description = "导出原始探测数据：/export [别名] [时间跨度=24h] [csv|json]")]
+    Export(String),
+    #[command(description = "查看 TLS 证书剩余天数")]
+    Certs,
+    #[command(description = "添加目标 (仅限管理员)：/add <别名> <地址> [类型]")]
+    Add(String),
+    #[command(description = "删除目标 (仅限管理员)：/remove <别名>")]
+    Remove(String),
+    #[command(description = "列出监测目标 (仅限管理员)")]
+    Targets,
+    #[command(description = "将目标分配给会话 (仅限管理员)：/assign <别名> [chat_id]")]
+    Assign(String),
+    #[command(description = "取消目标对会话的分配 (仅限管理员)：/unassign <别名> [chat_id]")]
+    Unassign(String),
+    #[command(description = "静音告警 (仅限管理员)：/mute <别名|all> <时长>")]
+    Mute(String),
+    #[command(description = "取消静音 (仅限管理员)：/unmute [别名|all]")]
+    Unmute(String),
+    #[command(description = "查看或设置接收的告警级别 (仅限管理员)")]
+    Alerts(String),
+    #[command(description = "查看静音期间错过的告警")]
+    Missed,
+    #[command(description = "查看或安排维护窗口 (仅限管理员)：/maintenance <别名> <开始时间> <时长>")]
+    Maintenance(String),
+    #[command(description = "查看故障记录：/incidents [别名] [天数]")]
+    Incidents(String),
+    #[command(description = "可用率报告：/sla [别名] [7d|30d|month|lastmonth]")]
+    Sla(String),
 }
 
 /// Mount this dispatcher in main.rs:
 ///
 /// Dispatcher::builder(bot.clone(), handler)
-///     .dependencies(dptree::deps![bot, cfg, db, targets])
+///     .dependencies(dptree::deps![bot, cfg, db, targets, limiter])
 ///     .enable_ctrlc_handler()
 ///     .build()
 ///     .dispatch()
 ///     .await;
-pub async fn cmd_dispatch(bot: Bot, cfg: Config, db: Arc<Db>, targets: Vec<(SocketAddr, String)>) {
+pub async fn cmd_dispatch(
+    bot: Bot,
+    cfg: Config,
+    db: Arc<Db>,
+    targets: Targets,
+    limiter: Arc<Semaphore>,
+) {
     let handler = Update::filter_message()
         .filter_command::<Command>()
         .endpoint(handle_cmd);
 
     Dispatcher::builder(bot.clone(), handler)
-        .dependencies(dptree::deps![bot, cfg, db, targets])
+        .dependencies(dptree::deps![bot, cfg, db, targets, limiter])
         .enable_ctrlc_handler()
         .build()
         .dispatch()
@@ -53,14 +92,20 @@ async fn handle_cmd(
     cmd: Command,
     cfg: Config,
     db: Arc<Db>,
-    targets: Vec<(SocketAddr, String)>,
+    targets: Targets,
+    limiter: Arc<Semaphore>,
 ) -> ResponseResult<()> {
     let chat_id = msg.chat.id;
-    // only group chats
-    if !matches!(msg.chat.kind, ChatKind::Public(_)) {
+    // only group chats; admins may also list targets in their private chat
+    let private = matches!(msg.chat.kind, ChatKind::Private(_));
+    let allowed = match msg.chat.kind {
+        ChatKind::Public(_) => true,
+        ChatKind::Private(_) => matches!(cmd, Command::Targets),
+    };
+    if !allowed {
         return Ok(());
     }
-    let user_id = match msg.from() {
+    let user_id = match msg.from.as_ref() {
         Some(u) => u.id.0 as i64,
         None => return Ok(()),
     };
@@ -75,17 +120,24 @@ async fn handle_cmd(
             isonline::isonline_command(
                 bot.clone(),
                 chat_id,
-                user_id,
                 &cfg,
                 Arc::clone(&db),
-                targets.clone(),
+                visible_targets(&db, &targets, chat_id).await,
+                limiter.clone(),
             )
             .await?;
         }
-        Command::Graph => {
+        Command::Graph(args) => {
             if db.is_subscribed(chat_id.0).await.unwrap_or(false) {
                 // Call graph_command and handle its Result directly
-                match graph::graph_command(bot.clone(), chat_id, db.clone()).await {
+                match graph::graph_command(
+                    bot.clone(),
+                    chat_id,
+                    db.clone(),
+                    visible_targets(&db, &targets, chat_id).await,
+                    &args,
+                )
+                .await {
                     Ok(()) => {
                         // success—nothing more to do
                     }
@@ -98,16 +150,108 @@ async fn handle_cmd(
                 }
             }
         }
-        Command::Uptime => {
-            match uptime::draw_uptime(bot.clone(),  chat_id, db.clone()).await {
+        Command::Uptime(args) => {
+            let visible = visible_targets(&db, &targets, chat_id).await;
+            match uptime::draw_uptime(bot.clone(), chat_id, db.clone(), visible, &args).await {
                 Ok(_) => {}
                 Err(e) => {
                     let _ = bot
-                        .send_message(chat_id, format!("❌ 绘制过去二小时在线状态失败: {}", e))
+                        .send_message(chat_id, format!("❌ 获取在线状态失败: {}", e))
                         .await;
                 }
             }
         }
+        Command::Dashboard(args) => {
+            let visible = visible_targets(&db, &targets, chat_id).await;
+            dashboard::dashboard_command(bot.clone(), chat_id, db.clone(), visible, &args).await?;
+        }
+        Command::Export(args) => {
+            let visible = visible_targets(&db, &targets, chat_id).await;
+            export::export_command(bot.clone(), chat_id, db.clone(), visible, &args).await?;
+        }
+        Command::Certs => {
+            let visible = visible_targets(&db, &targets, chat_id).await;
+            certs::certs_command(bot.clone(), chat_id, db.clone(), visible).await?;
+        }
+        Command::Add(args) => {
+            add::add_command(bot.clone(), chat_id, user_id, &cfg, db.clone(), targets, &args)
+                .await?;
+        }
+        Command::Remove(alias) => {
+            remove::remove_command(bot.clone(), chat_id, user_id, &cfg, db.clone(), targets, &alias)
+                .await?;
+        }
+        Command::Targets => {
+            targets::targets_command(bot.clone(), chat_id, user_id, private, &cfg, db.clone(), targets)
+                .await?;
+        }
+        Command::Assign(args) => {
+            assign::assign_command(
+                bot.clone(),
+                chat_id,
+                user_id,
+                &cfg,
+                db.clone(),
+                targets,
+                &args,
+                true,
+            )
+            .await?;
+        }
+        Command::Unassign(args) => {
+            assign::assign_command(
+                bot.clone(),
+                chat_id,
+                user_id,
+                &cfg,
+                db.clone(),
+                targets,
+                &args,
+                false,
+            )
+            .await?;
+        }
+        Command::Mute(args) => {
+            mute::mute_command(bot.clone(), chat_id, user_id, &cfg, db.clone(), targets, &args)
+                .await?;
+        }
+        Command::Unmute(args) => {
+            mute::unmute_command(bot.clone(), chat_id, user_id, &cfg, db.clone(), &args).await?;
+        }
+        Command::Alerts(args) => {
+            alerts::alerts_command(bot.clone(), chat_id, user_id, &cfg, db.clone(), &args).await?;
+        }
+        Command::Missed => {
+            missed::missed_command(bot.clone(), chat_id, db.clone()).await?;
+        }
+        Command::Maintenance(args) => {
+            maintenance::maintenance_command(
+                bot.clone(),
+                chat_id,
+                user_id,
+                &cfg,
+                db.clone(),
+                targets,
+                &args,
+            )
+            .await?;
+        }
+        Command::Incidents(args) => {
+            let visible = visible_targets(&db, &targets, chat_id).await;
+            incidents::incidents_command(bot.clone(), chat_id, db.clone(), visible, &args).await?;
+        }
+        Command::Sla(args) => {
+            let visible = visible_targets(&db, &targets, chat_id).await;
+            sla::sla_command(bot.clone(), chat_id, &cfg, db.clone(), visible, &args).await?;
+        }
     }
     Ok(())
 }
+
+/// 当前会话可见的目标，读取归属失败时不显示任何目标
+async fn visible_targets(db: &Db, targets: &Targets, chat_id: ChatId) -> Vec<TargetConfig> {
+    targets.visible_to(db, chat_id.0).await.unwrap_or_else(|e| {
+        log::error!("读取目标归属失败 [{}]: {}", chat_id, e);
+        Vec::new()
+    })
+}
@@ -0,0 +1,69 @@
+use crate::commands::isonline::CmdResult;
+use crate::config::{Config, ProbeKind, TargetConfig};
+use crate::db::Db;
+use crate::probe;
+use crate::targets::Targets;
+use std::sync::Arc;
+use teloxide::prelude::Requester;
+use teloxide::types::ChatId;
+use teloxide::Bot;
+
+const USAGE: &str = "用法：/add <别名> <地址> [tcp|http|tls|dns|udp]";
+
+/// Handle the `/add` command: add a target at runtime and persist it
+pub async fn add_command(
+    bot: Bot,
+    chat_id: ChatId,
+    user_id: i64,
+    cfg: &Config,
+    db: Arc<Db>,
+    targets: Targets,
+    args: &str,
+) -> CmdResult {
+    if !cfg.admins.contains(&user_id) {
+        return Ok(());
+    }
+
+    let parts: Vec<&str> = args.split_whitespace().collect();
+    let (alias, address, kind) = match parts.as_slice() {
+        [alias, address] => (*alias, *address, Ok(ProbeKind::default())),
+        [alias, address, kind] => (*alias, *address, kind.parse::<ProbeKind>()),
+        _ => {
+            bot.send_message(chat_id, USAGE).await?;
+            return Ok(());
+        }
+    };
+    let kind = match kind.and_then(|k| probe::validate_address(k, address).map(|_| k)) {
+        Ok(kind) => kind,
+        Err(e) => {
+            bot.send_message(chat_id, format!("❌ {}\n{}", e, USAGE)).await?;
+            return Ok(());
+        }
+    };
+    if targets.contains(alias) {
+        bot.send_message(chat_id, format!("❌ 目标 {} 已存在", alias))
+            .await?;
+        return Ok(());
+    }
+
+    let target = TargetConfig::new(alias, address, kind);
+    match db.add_target(&target).await {
+        Ok(true) => {
+            targets.add(target);
+            bot.send_message(
+                chat_id,
+                format!("✅ 已添加目标 {}（{} {}）", alias, kind.as_str(), address),
+            )
+            .await?;
+        }
+        Ok(false) => {
+            bot.send_message(chat_id, format!("❌ 目标 {} 已存在", alias))
+                .await?;
+        }
+        Err(e) => {
+            bot.send_message(chat_id, format!("❌ 保存目标失败: {}", e))
+                .await?;
+        }
+    }
+    Ok(())
+}
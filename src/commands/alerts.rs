@@ -0,0 +1,87 @@
+use crate::alert::{Severity, ALL_TARGETS};
+use crate::commands::isonline::CmdResult;
+use crate::config::Config;
+use crate::db::Db;
+use anyhow::Result;
+use chrono::Local;
+use std::sync::Arc;
+use teloxide::prelude::Requester;
+use teloxide::types::ChatId;
+use teloxide::Bot;
+
+const USAGE: &str = "用法：/alerts [all | down,degraded,recovered,cert]";
+
+/// 解析级别列表，`all` 表示全部
+fn parse_severities(args: &str) -> Result<Option<Vec<Severity>>> {
+    if args.trim().eq_ignore_ascii_case("all") {
+        return Ok(None);
+    }
+    let mut list = Vec::new();
+    for name in args.split(|c: char| c == ',' || c.is_whitespace()) {
+        if name.is_empty() {
+            continue;
+        }
+        let severity = name.parse::<Severity>()?;
+        if !list.contains(&severity) {
+            list.push(severity);
+        }
+    }
+    Ok(Some(list))
+}
+
+/// Handle the `/alerts` command: show or choose which severities this chat receives
+pub async fn alerts_command(
+    bot: Bot,
+    chat_id: ChatId,
+    user_id: i64,
+    cfg: &Config,
+    db: Arc<Db>,
+    args: &str,
+) -> CmdResult {
+    if !cfg.admins.contains(&user_id) {
+        return Ok(());
+    }
+
+    if !args.trim().is_empty() {
+        let severities = match parse_severities(args) {
+            Ok(s) => s,
+            Err(e) => {
+                bot.send_message(chat_id, format!("❌ {}\n{}", e, USAGE)).await?;
+                return Ok(());
+            }
+        };
+        if let Err(e) = db.set_severities(chat_id.0, severities.as_deref()).await {
+            bot.send_message(chat_id, format!("❌ 保存设置失败: {}", e))
+                .await?;
+            return Ok(());
+        }
+    }
+
+    let settings = match db.chat_settings(chat_id.0).await {
+        Ok(s) => s,
+        Err(e) => {
+            bot.send_message(chat_id, format!("❌ 读取设置失败: {}", e))
+                .await?;
+            return Ok(());
+        }
+    };
+    let mut report = String::from("🔔 告警设置：\n");
+    for severity in Severity::ALL {
+        let mark = if settings.accepts(severity) { "✔" } else { "✖" };
+        report.push_str(&format!(
+            "{} {} ({})\n",
+            mark,
+            severity.label(),
+            severity.as_str()
+        ));
+    }
+    for (alias, until) in &settings.mutes {
+        report.push_str(&format!(
+            "🔕 {} 静音至 {}\n",
+            if alias == ALL_TARGETS { "全部目标" } else { alias },
+            until.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S")
+        ));
+    }
+    bot.send_message(chat_id, report).await?;
+    Ok(())
+}
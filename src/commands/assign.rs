@@ -0,0 +1,67 @@
+use crate::commands::isonline::CmdResult;
+use crate::config::Config;
+use crate::db::Db;
+use crate::targets::Targets;
+use std::sync::Arc;
+use teloxide::prelude::Requester;
+use teloxide::types::ChatId;
+use teloxide::Bot;
+
+/// 解析 `<别名> [chat_id]`，chat_id 缺省为当前会话
+fn parse_args(args: &str, chat_id: ChatId) -> Option<(&str, i64)> {
+    let mut parts = args.split_whitespace();
+    let alias = parts.next()?;
+    let chat = match parts.next() {
+        Some(id) => id.parse().ok()?,
+        None => chat_id.0,
+    };
+    parts.next().is_none().then_some((alias, chat))
+}
+
+/// Handle the `/assign` and `/unassign` commands: scope a target to chats
+#[allow(clippy::too_many_arguments)]
+pub async fn assign_command(
+    bot: Bot,
+    chat_id: ChatId,
+    user_id: i64,
+    cfg: &Config,
+    db: Arc<Db>,
+    targets: Targets,
+    args: &str,
+    assign: bool,
+) -> CmdResult {
+    if !cfg.admins.contains(&user_id) {
+        return Ok(());
+    }
+
+    let Some((alias, chat)) = parse_args(args, chat_id) else {
+        let usage = if assign {
+            "用法：/assign <别名> [chat_id]"
+        } else {
+            "用法：/unassign <别名> [chat_id]"
+        };
+        bot.send_message(chat_id, usage).await?;
+        return Ok(());
+    };
+    if !targets.contains(alias) {
+        bot.send_message(chat_id, format!("❌ 目标 {} 不存在", alias))
+            .await?;
+        return Ok(());
+    }
+
+    let text = if assign {
+        match db.assign_target(alias, chat).await {
+            Ok(true) => format!("✅ 已将 {} 分配给会话 {}", alias, chat),
+            Ok(false) => format!("{} 已分配给会话 {}", alias, chat),
+            Err(e) => format!("❌ 分配失败: {}", e),
+        }
+    } else {
+        match db.unassign_target(alias, chat).await {
+            Ok(true) => format!("✅ 已取消 {} 对会话 {} 的分配", alias, chat),
+            Ok(false) => format!("{} 未通过命令分配给会话 {}", alias, chat),
+            Err(e) => format!("❌ 取消分配失败: {}", e),
+        }
+    };
+    bot.send_message(chat_id, text).await?;
+    Ok(())
+}
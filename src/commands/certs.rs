@@ -0,0 +1,50 @@
+use crate::commands::isonline::CmdResult;
+use crate::config::{ProbeKind, TargetConfig};
+use crate::db::Db;
+use chrono::{Local, Utc};
+use std::sync::Arc;
+use teloxide::prelude::Requester;
+use teloxide::types::ChatId;
+use teloxide::Bot;
+
+/// Handle the `/certs` command: days remaining for every TLS target
+pub async fn certs_command(
+    bot: Bot,
+    chat_id: ChatId,
+    db: Arc<Db>,
+    targets: Vec<TargetConfig>,
+) -> CmdResult {
+    if !db.is_subscribed(chat_id.0).await.unwrap_or(false) {
+        return Ok(());
+    }
+
+    let tls_targets: Vec<&TargetConfig> =
+        targets.iter().filter(|t| t.kind == ProbeKind::Tls).collect();
+    if tls_targets.is_empty() {
+        bot.send_message(chat_id, "未配置 TLS 目标").await?;
+        return Ok(());
+    }
+
+    let now = Utc::now();
+    let mut report = String::from("🔐 TLS 证书状态：\n");
+    for t in tls_targets {
+        let line = match db.get_cert(&t.alias).await {
+            Ok(Some((not_after, _checked_at))) => {
+                let days_left = (not_after - now).num_days();
+                let mark = if days_left <= t.cert_warn_days() { "⚠️" } else { "✔" };
+                format!(
+                    "{}: {} 剩余 {} 天（到期 {}）\n",
+                    t.alias,
+                    mark,
+                    days_left,
+                    not_after.with_timezone(&Local).format("%Y-%m-%d")
+                )
+            }
+            Ok(None) => format!("{}: 暂无数据\n", t.alias),
+            Err(e) => format!("{}: 查询失败: {}\n", t.alias, e),
+        };
+        report.push_str(&line);
+    }
+    bot.send_message(chat_id, report).await?;
+    Ok(())
+}
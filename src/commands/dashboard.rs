@@ -0,0 +1,111 @@
+use crate::alert::format_duration;
+use crate::commands::graph::{chart_query, parse_range, render_grid, split_points, Axis, Panel};
+use crate::commands::isonline::CmdResult;
+use crate::config::TargetConfig;
+use crate::db::Db;
+use chrono::{Duration, Utc};
+use std::sync::Arc;
+use teloxide::payloads::SendPhotoSetters;
+use teloxide::prelude::Requester;
+use teloxide::types::{ChatId, InputFile};
+use teloxide::Bot;
+use tokio::task;
+
+const USAGE: &str = "用法：/dashboard [时间跨度=6h]";
+/// 一张图最多包含的目标数
+const MAX_TARGETS: usize = 16;
+/// 每行的目标数
+const COLUMNS: usize = 2;
+
+/// Handle the `/dashboard` command: latency and packet loss of every visible target in one image
+pub async fn dashboard_command(
+    bot: Bot,
+    chat_id: ChatId,
+    db: Arc<Db>,
+    targets: Vec<TargetConfig>,
+    args: &str,
+) -> CmdResult {
+    if !db.is_subscribed(chat_id.0).await.unwrap_or(false) {
+        return Ok(());
+    }
+    let range = match args.split_whitespace().collect::<Vec<_>>().as_slice() {
+        [] => Duration::hours(6),
+        [range] => match parse_range(range) {
+            Ok(r) => r,
+            Err(e) => {
+                bot.send_message(chat_id, format!("❌ {}\n{}", e, USAGE)).await?;
+                return Ok(());
+            }
+        },
+        _ => {
+            bot.send_message(chat_id, USAGE).await?;
+            return Ok(());
+        }
+    };
+    if targets.is_empty() {
+        bot.send_message(chat_id, "暂无监测目标").await?;
+        return Ok(());
+    }
+
+    let now = Utc::now();
+    let since = now - range;
+    let axis = Axis::new(since, range);
+    let shown: Vec<&TargetConfig> = targets.iter().take(MAX_TARGETS).collect();
+    let query = chart_query(&axis, range, shown.iter().map(|t| t.alias.as_str()));
+    let points = match db.query(query).await {
+        Ok(points) => points,
+        Err(e) => {
+            bot.send_message(chat_id, format!("❌ 读取数据失败: {}", e)).await?;
+            return Ok(());
+        }
+    };
+    let (mut latency, mut loss, mut outages) = split_points(points, &axis);
+
+    // 每个目标一格：上方延迟，下方丢包阴影
+    let cells: Vec<Vec<Panel>> = shown
+        .iter()
+        .map(|t| {
+            let alias = &t.alias;
+            vec![
+                Panel {
+                    title: alias.clone(),
+                    y_label: String::from("延迟 (ms)"),
+                    series: latency.remove_entry(alias).into_iter().collect(),
+                    fill: false,
+                    outages: outages.remove(alias).unwrap_or_default(),
+                    y_max: None,
+                    dim: [800.0, 400.0],
+                },
+                Panel {
+                    title: String::new(),
+                    y_label: String::from("丢包率 (%)"),
+                    series: loss.remove_entry(alias).into_iter().collect(),
+                    fill: true,
+                    outages: Vec::new(),
+                    y_max: Some(100.0),
+                    dim: [800.0, 300.0],
+                },
+            ]
+        })
+        .collect();
+
+    let rendered = task::spawn_blocking(move || render_grid(&cells, &axis, COLUMNS))
+        .await
+        .map_err(anyhow::Error::from)
+        .and_then(|r| r);
+    let png = match rendered {
+        Ok(png) => png,
+        Err(e) => {
+            bot.send_message(chat_id, format!("❌ 绘制图表失败: {}", e)).await?;
+            return Ok(());
+        }
+    };
+    let mut caption = format!("过去 {} 延迟与丢包总览", format_duration(range));
+    if targets.len() > MAX_TARGETS {
+        caption.push_str(&format!("（仅显示前 {} 个目标，共 {} 个）", MAX_TARGETS, targets.len()));
+    }
+    bot.send_photo(chat_id, InputFile::memory(png).file_name("dashboard.png"))
+        .caption(caption)
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,109 @@
+use crate::alert::format_duration;
+use crate::commands::isonline::CmdResult;
+use crate::config::TargetConfig;
+use crate::db::Db;
+use crate::export::{export_since, write_records, ExportArgs};
+use chrono::{Local, Utc};
+use std::sync::Arc;
+use teloxide::payloads::SendDocumentSetters;
+use teloxide::prelude::Requester;
+use teloxide::types::{ChatId, InputFile};
+use teloxide::Bot;
+use tokio::task;
+
+const USAGE: &str = "用法：/export [别名] [时间跨度=24h] [csv|json]";
+
+/// Handle the `/export` command: send the raw metrics of visible targets as a CSV / JSON document
+pub async fn export_command(
+    bot: Bot,
+    chat_id: ChatId,
+    db: Arc<Db>,
+    targets: Vec<TargetConfig>,
+    args: &str,
+) -> CmdResult {
+    if !db.is_subscribed(chat_id.0).await.unwrap_or(false) {
+        return Ok(());
+    }
+    let args = match ExportArgs::parse(args) {
+        Ok(args) => args,
+        Err(e) => {
+            bot.send_message(chat_id, format!("❌ {}\n{}", e, USAGE)).await?;
+            return Ok(());
+        }
+    };
+    let aliases: Vec<String> = match &args.alias {
+        Some(alias) if targets.iter().any(|t| &t.alias == alias) => vec![alias.clone()],
+        Some(alias) => {
+            let known: Vec<&str> = targets.iter().map(|t| t.alias.as_str()).collect();
+            bot.send_message(
+                chat_id,
+                format!("❌ 未知目标: {}（可选：{}）\n{}", alias, known.join(", "), USAGE),
+            )
+            .await?;
+            return Ok(());
+        }
+        None => targets.iter().map(|t| t.alias.clone()).collect(),
+    };
+    if aliases.is_empty() {
+        bot.send_message(chat_id, "暂无监测目标").await?;
+        return Ok(());
+    }
+
+    let now = Utc::now();
+    let range = format_duration(args.range);
+    let (since, clamped) = match export_since(&db, args.range, now).await {
+        Ok(v) => v,
+        Err(e) => {
+            bot.send_message(chat_id, format!("❌ 读取数据失败: {}", e)).await?;
+            return Ok(());
+        }
+    };
+    let records = match db.metric_records(since, now, Some(aliases)).await {
+        Ok(records) => records,
+        Err(e) => {
+            bot.send_message(chat_id, format!("❌ 读取数据失败: {}", e)).await?;
+            return Ok(());
+        }
+    };
+    if records.is_empty() {
+        bot.send_message(chat_id, format!("过去 {} 没有探测记录", range)).await?;
+        return Ok(());
+    }
+
+    let (count, format) = (records.len(), args.format);
+    let serialized = task::spawn_blocking(move || {
+        let mut buf = Vec::new();
+        write_records(&records, format, &mut buf).map(|_| buf)
+    })
+    .await
+    .map_err(anyhow::Error::from)
+    .and_then(|r| r);
+    let data = match serialized {
+        Ok(data) => data,
+        Err(e) => {
+            bot.send_message(chat_id, format!("❌ 导出失败: {}", e)).await?;
+            return Ok(());
+        }
+    };
+    let file_name = format!(
+        "metrics_{}_{}.{}",
+        args.alias.as_deref().unwrap_or("all"),
+        now.with_timezone(&Local).format("%Y%m%d%H%M"),
+        format.extension()
+    );
+    let name = args.alias.as_deref().unwrap_or("全部目标");
+    let caption = if clamped {
+        format!(
+            "{} 自 {} 起的探测记录，共 {} 条\n⚠️ 更早的原始数据已被清理（仅保留汇总），未包含在导出中",
+            name,
+            since.with_timezone(&Local).format("%Y-%m-%d %H:%M"),
+            count
+        )
+    } else {
+        format!("{} 过去 {} 的探测记录，共 {} 条", name, range, count)
+    };
+    bot.send_document(chat_id, InputFile::memory(data).file_name(file_name))
+        .caption(caption)
+        .await?;
+    Ok(())
+}
@@ -1,122 +1,508 @@
 // commands/graph.rs
 
-use crate::db::Db;
-use chrono::{DateTime, Duration, NaiveDateTime, Utc};
-use image::{DynamicImage, ImageBuffer, RgbImage, ImageFormat};  // ← note ImageFormat
-use poloto::prelude::*; // Figure, build::plot, frame_build, header, Label, Theme :contentReference[oaicite:0]{index=0}
-use poloto::{build, frame_build, header, ticks};
+use crate::config::TargetConfig;
+use crate::alert::{format_duration, parse_duration};
+use crate::db::{Aggregate, Db, MetricPoint, MetricQuery};
+use anyhow::anyhow;
+use chrono::{DateTime, Duration, Utc};
+use once_cell::sync::Lazy;
+use poloto::{build, header, ticks};
 use resvg::tiny_skia;
 use resvg::usvg;
-use std::{collections::BTreeMap, fs, path::PathBuf, sync::Arc};
+use std::{collections::BTreeMap, sync::Arc};
 use teloxide::{prelude::Requester, types::InputFile, Bot};
 use tokio::task;
-use std::io::{BufReader, Read};
-use std::fs::File;
-use fontdb::Database;
-use usvg::{Tree};
-use usvg_text_layout::convert_text;
-
-/// Convert an SVG file at `svg_path` into a raster image at `out_path`
-/// keeping the same width/height, format chosen by extension (jpg/png).
-
-fn convert_svg_to_image(svg_path: &str, out_path: &str) -> Result<(), Box<dyn std::error::Error>> {
-    // 1. Read SVG data
-    let mut file = File::open(svg_path)?;
-    let mut data = Vec::new();
-    file.read_to_end(&mut data)?;
-
-    // 2. Parse with font support
-    let mut opt = usvg::Options::default();
-    // CORRECT: get a mutable reference to the inner Database, then load fonts
-    opt.fontdb_mut().load_system_fonts();    // &mut Database → you can now call load_system_fonts() :contentReference[oaicite:0]{index=0}
-    let tree = usvg::Tree::from_data(&data, &opt)?;
-    // 3. Rasterize at the SVG’s own size
-    let size = tree.size();
-    let (w, h) = (size.width().round() as u32, size.height().round() as u32);
-    let mut pixmap = tiny_skia::Pixmap::new(w, h).ok_or("pixmap failed")?;
+use std::str::FromStr;
+
+/// 候选的无衬线字体，按优先级排列
+const SANS_SERIF: &[&str] = &[
+    "Noto Sans CJK SC",
+    "Source Han Sans SC",
+    "WenQuanYi Micro Hei",
+    "Microsoft YaHei",
+    "Arial",
+    "DejaVu Sans",
+];
+
+/// 系统字体只加载一次，所有图表共用
+static FONTDB: Lazy<Arc<usvg::fontdb::Database>> = Lazy::new(|| {
+    let mut db = usvg::fontdb::Database::new();
+    db.load_system_fonts();
+    // poloto 使用 sans-serif，fontdb 默认映射到 Arial；优先改用能显示中文的字体
+    let installed = |family: &str| {
+        db.faces()
+            .any(|face| face.families.iter().any(|(name, _)| name == family))
+    };
+    if let Some(family) = SANS_SERIF.iter().copied().find(|f| installed(f)) {
+        db.set_sans_serif_family(family);
+    }
+    Arc::new(db)
+});
+
+/// 启动时预先加载字体，返回字体数量
+pub fn load_fonts() -> usize {
+    FONTDB.len()
+}
+
+const USAGE: &str = "用法：/graph [别名...] [range=1h] [metric=latency|loss|p95|min|max]";
+/// 单张图最长时间跨度
+const MAX_RANGE_DAYS: i64 = 30;
+
+/// 可绘制的指标
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Metric {
+    Latency,
+    Loss,
+    P95,
+    /// 每个时间桶内的最低延迟
+    Min,
+    /// 每个时间桶内的最高延迟
+    Max,
+}
+
+impl Metric {
+    fn value(self, point: &MetricPoint) -> Option<f64> {
+        match self {
+            Metric::Latency | Metric::Min | Metric::Max => point.latency,
+            Metric::Loss => Some(point.loss),
+            Metric::P95 => point.p95,
+        }
+    }
+
+    fn aggregate(self) -> Aggregate {
+        match self {
+            Metric::Min => Aggregate::Min,
+            Metric::Max => Aggregate::Max,
+            _ => Aggregate::Avg,
+        }
+    }
+
+    fn title(self) -> &'static str {
+        match self {
+            Metric::Latency => "延迟",
+            Metric::Loss => "丢包率",
+            Metric::P95 => "P95 延迟",
+            Metric::Min => "最低延迟",
+            Metric::Max => "最高延迟",
+        }
+    }
+
+    fn unit(self) -> &'static str {
+        match self {
+            Metric::Latency | Metric::P95 | Metric::Min | Metric::Max => "ms",
+            Metric::Loss => "%",
+        }
+    }
+}
+
+impl FromStr for Metric {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "latency" => Ok(Metric::Latency),
+            "loss" => Ok(Metric::Loss),
+            "p95" => Ok(Metric::P95),
+            "min" => Ok(Metric::Min),
+            "max" => Ok(Metric::Max),
+            _ => Err(anyhow!("未知指标: {}（可选 latency/loss/p95/min/max）", s)),
+        }
+    }
+}
+
+/// `/graph` 的参数
+#[derive(Debug)]
+pub struct GraphArgs {
+    /// 为空时绘制全部可见目标
+    pub aliases: Vec<String>,
+    pub range: Duration,
+    pub metric: Metric,
+}
+
+/// 解析时间跨度，限制在 [1 分钟, 30 天]
+pub(crate) fn parse_range(s: &str) -> anyhow::Result<Duration> {
+    let range = parse_duration(s)?;
+    if range < Duration::minutes(1) || range > Duration::days(MAX_RANGE_DAYS) {
+        return Err(anyhow!("时间跨度需在 1m 到 {}d 之间: {}", MAX_RANGE_DAYS, s));
+    }
+    Ok(range)
+}
+
+/// 解析 `/graph` 参数：`key=value` 或裸值，裸值依次尝试时长、指标、目标别名
+pub fn parse_graph_args(args: &str, targets: &[TargetConfig]) -> anyhow::Result<GraphArgs> {
+    let mut parsed = GraphArgs {
+        aliases: Vec::new(),
+        range: Duration::hours(1),
+        metric: Metric::Latency,
+    };
+    for arg in args.split_whitespace() {
+        match arg.split_once('=') {
+            Some(("range", v)) => parsed.range = parse_range(v)?,
+            Some(("metric", v)) => parsed.metric = v.parse()?,
+            Some((k, _)) => return Err(anyhow!("未知参数: {}（可选 range、metric）", k)),
+            None => {
+                if let Ok(range) = parse_range(arg) {
+                    parsed.range = range;
+                } else if let Ok(metric) = arg.parse() {
+                    parsed.metric = metric;
+                } else if targets.iter().any(|t| t.alias == arg) {
+                    parsed.aliases.push(arg.to_string());
+                } else {
+                    let known: Vec<&str> = targets.iter().map(|t| t.alias.as_str()).collect();
+                    return Err(anyhow!(
+                        "未知目标: {}（可选：{}）",
+                        arg,
+                        known.join(", ")
+                    ));
+                }
+            }
+        }
+    }
+    Ok(parsed)
+}
+
+/// X 轴：以 `since` 为原点，按 `unit` 秒为一个单位
+#[derive(Debug, Clone)]
+pub(crate) struct Axis {
+    pub since: DateTime<Utc>,
+    pub unit: f64,
+    pub unit_name: &'static str,
+    /// 总跨度（单位数）
+    pub span: f64,
+    pub ticks: Vec<f64>,
+}
+
+impl Axis {
+    /// 按时间跨度挑选 X 轴单位与刻度
+    ///
+    /// 刻度从右端（现在）起按整步长向左排列。
+    pub fn new(since: DateTime<Utc>, range: Duration) -> Self {
+        let secs = range.num_seconds().max(60) as f64;
+        let (unit, unit_name, steps): (f64, &str, &[f64]) = if secs <= 3.0 * 3600.0 {
+            (60.0, "分钟", &[1.0, 2.0, 5.0, 10.0, 15.0, 20.0, 30.0, 60.0])
+        } else if secs <= 3.0 * 86400.0 {
+            (3600.0, "小时", &[1.0, 2.0, 3.0, 6.0, 12.0, 24.0])
+        } else {
+            (86400.0, "天", &[1.0, 2.0, 5.0, 7.0, 10.0, 15.0, 30.0])
+        };
+        let span = secs / unit;
+        let step = steps
+            .iter()
+            .copied()
+            .find(|step| span / step <= 8.0)
+            .unwrap_or(span / 4.0);
+        let mut ticks: Vec<f64> = (0..)
+            .map(|k| span - k as f64 * step)
+            .take_while(|&v| v >= -1e-9)
+            .collect();
+        ticks.reverse();
+        Axis {
+            since,
+            unit,
+            unit_name,
+            span,
+            ticks,
+        }
+    }
+
+    /// 时间点对应的 x 坐标
+    pub fn x(&self, ts: DateTime<Utc>) -> f64 {
+        (ts - self.since).num_seconds() as f64 / self.unit
+    }
+}
+
+/// alias -> 点序列
+pub(crate) type Series = BTreeMap<String, Vec<(f64, f64)>>;
+
+/// 一个图表面板
+pub(crate) struct Panel {
+    pub title: String,
+    pub y_label: String,
+    pub series: Series,
+    /// 以阴影填充代替折线（用于丢包率）
+    pub fill: bool,
+    /// 离线轮次的 x 坐标，标在 y=0 处
+    pub outages: Vec<f64>,
+    /// 固定的 y 轴上限，例如丢包率 100
+    pub y_max: Option<f64>,
+    /// 画布尺寸；poloto 上下各留 100 的边距，高度需明显大于 200
+    pub dim: [f64; 2],
+}
+
+impl Panel {
+    /// 用 poloto 绘制为 SVG 文本
+    pub fn render_svg(&self, axis: &Axis) -> anyhow::Result<String> {
+        let fill = self.fill;
+        let plots = self.series.iter().map(move |(alias, pts)| {
+            let plot = build::plot(alias.clone());
+            if fill {
+                plot.line_fill(pts.clone().into_iter())
+            } else {
+                plot.line(pts.clone().into_iter())
+            }
+        });
+        let outage_label = if self.outages.is_empty() { "" } else { "离线" };
+        let outages = build::plot(outage_label).scatter(
+            self.outages
+                .iter()
+                .map(|&x| (x, 0.0))
+                .collect::<Vec<_>>()
+                .into_iter(),
+        );
+        // 固定 x 轴为整个时间跨度，y 轴包含 0（及上限）
+        let bounds = build::markers([0.0, axis.span], [0.0].into_iter().chain(self.y_max));
+
+        let span = axis.span;
+        let x_ticks = ticks::from_iter(axis.ticks.clone()).with_tick_fmt(move |&v| {
+            let ago = (span - v).round() as i64;
+            if ago == 0 {
+                String::from("now")
+            } else {
+                ago.to_string()
+            }
+        });
+
+        let x_label = format!("时间 ({}前)", axis.unit_name);
+        let svg = poloto::frame()
+            .with_viewbox(self.dim)
+            .build()
+            .data(poloto::plots!(bounds, outages, plots))
+            .map_xticks(|_| x_ticks)
+            .build_and_label((self.title.as_str(), x_label.as_str(), self.y_label.as_str()))
+            .append_to(header().with_dim(self.dim).with_viewbox(self.dim).light_theme())
+            .render_string()?;
+        Ok(svg)
+    }
+}
+
+/// 把 SVG 文本栅格化为同尺寸、白底的位图
+fn render_pixmap(svg: &str) -> anyhow::Result<tiny_skia::Pixmap> {
+    let opt = usvg::Options {
+        fontdb: FONTDB.clone(),
+        ..Default::default()
+    };
+    let tree = usvg::Tree::from_str(svg, &opt)?;
+    let size = tree.size().to_int_size();
+    let mut pixmap = tiny_skia::Pixmap::new(size.width(), size.height())
+        .ok_or_else(|| anyhow!("无法创建 {}x{} 画布", size.width(), size.height()))?;
+    pixmap.fill(tiny_skia::Color::WHITE);
     resvg::render(&tree, tiny_skia::Transform::default(), &mut pixmap.as_mut());
+    Ok(pixmap)
+}
 
-    // 4. Save as JPEG (or PNG)
-    let buffer: RgbImage = ImageBuffer::from_fn(w, h, |x, y| {
-        let p = pixmap.pixel(x, y).unwrap();
-        image::Rgb([p.red(), p.green(), p.blue()])
-    });
-    let dyn_img = DynamicImage::ImageRgb8(buffer);
-    let mut out = File::create(out_path)?;
-    dyn_img.write_to(&mut out, ImageFormat::Jpeg)?;
+/// 把若干面板按 `columns` 列拼成一张 PNG；同一格内的多个面板纵向堆叠
+pub(crate) fn render_grid(cells: &[Vec<Panel>], axis: &Axis, columns: usize) -> anyhow::Result<Vec<u8>> {
+    let mut images = Vec::with_capacity(cells.len());
+    for cell in cells {
+        let mut stacked = Vec::with_capacity(cell.len());
+        for panel in cell {
+            stacked.push(render_pixmap(&panel.render_svg(axis)?)?);
+        }
+        images.push(compose(&stacked, 1)?);
+    }
+    Ok(compose(&images, columns.max(1))?.encode_png()?)
+}
 
-    Ok(())
+/// 按网格拼接位图，每列取该列最宽、每行取该行最高
+fn compose(images: &[tiny_skia::Pixmap], columns: usize) -> anyhow::Result<tiny_skia::Pixmap> {
+    let col_width: Vec<u32> = (0..columns)
+        .map(|c| images.iter().skip(c).step_by(columns).map(|p| p.width()).max().unwrap_or(0))
+        .collect();
+    let row_height: Vec<u32> = images
+        .chunks(columns)
+        .map(|row| row.iter().map(|p| p.height()).max().unwrap_or(0))
+        .collect();
+    let (w, h) = (col_width.iter().sum::<u32>(), row_height.iter().sum::<u32>());
+    let mut canvas = tiny_skia::Pixmap::new(w, h).ok_or_else(|| anyhow!("无法创建 {}x{} 画布", w, h))?;
+    canvas.fill(tiny_skia::Color::WHITE);
+    let mut y = 0;
+    for (row, height) in images.chunks(columns).zip(&row_height) {
+        let mut x = 0;
+        for (image, width) in row.iter().zip(&col_width) {
+            canvas.draw_pixmap(
+                x as i32,
+                y as i32,
+                image.as_ref(),
+                &tiny_skia::PixmapPaint::default(),
+                tiny_skia::Transform::identity(),
+                None,
+            );
+            x += width;
+        }
+        y += height;
+    }
+    Ok(canvas)
 }
-/// 从过去一小时数据生成延迟折线图并发送（基于 poloto 19.1.2）
-// … all your existing imports and convert_svg_to_image stay exactly the same …
 
+/// 折线中的断点：poloto 遇到非有限值会断开线段
+const GAP: f64 = f64::NAN;
+/// 每条曲线最多的点数，跨度较大时在 SQL 中分桶取平均
+const MAX_POINTS: i64 = 500;
+
+/// 绘图用的查询：跨度较大时按整分钟分桶，每条曲线不超过 `MAX_POINTS` 个点
+pub(crate) fn chart_query<I, S>(axis: &Axis, range: Duration, aliases: I) -> MetricQuery
+where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+{
+    let query = MetricQuery::new(axis.since, axis.since + range).aliases(aliases);
+    let minutes = (range.num_seconds() / MAX_POINTS + 59) / 60;
+    if minutes > 1 {
+        query.bucket(Duration::minutes(minutes))
+    } else {
+        query
+    }
+}
+
+/// 按目标拆分延迟、丢包与离线点；没有延迟的点在折线上断开并记为离线
+pub(crate) fn split_points(
+    points: Vec<MetricPoint>,
+    axis: &Axis,
+) -> (Series, Series, BTreeMap<String, Vec<f64>>) {
+    let (mut latency, mut loss, mut outages) = (Series::new(), Series::new(), BTreeMap::new());
+    for point in points {
+        let x = axis.x(point.ts);
+        if point.latency.is_none() {
+            outages.entry(point.alias.clone()).or_insert_with(Vec::new).push(x);
+        }
+        latency
+            .entry(point.alias.clone())
+            .or_default()
+            .push((x, point.latency.unwrap_or(GAP)));
+        loss.entry(point.alias).or_default().push((x, point.loss));
+    }
+    (latency, loss, outages)
+}
+
+/// 按参数绘制折线图并发送（基于 poloto 19.1.2）
 pub async fn graph_command(
     bot: Bot,
     chat_id: teloxide::types::ChatId,
     db: Arc<Db>,
+    targets: Vec<TargetConfig>,
+    args: &str,
 ) -> anyhow::Result<()> {
-    // 1. Fetch and prepare data exactly as you already do…
-    let now   = Utc::now();
-    let since = now - Duration::hours(1);
-    let rows  = db.query_metrics(since).await.unwrap_or_default();
+    let args = match parse_graph_args(args, &targets) {
+        Ok(a) => a,
+        Err(e) => {
+            bot.send_message(chat_id, format!("❌ {}\n{}", e, USAGE)).await?;
+            return Ok(());
+        }
+    };
+    let selected: Vec<String> = if args.aliases.is_empty() {
+        targets.iter().map(|t| t.alias.clone()).collect()
+    } else {
+        args.aliases.clone()
+    };
 
+    // 1. 取数：x 为距起点的单位数
+    let now = Utc::now();
+    let since = now - args.range;
+    let axis = Axis::new(since, args.range);
+    let range = format_duration(args.range);
+    let panels = match args.metric {
+        Metric::Latency | Metric::P95 | Metric::Min | Metric::Max => {
+            let query = chart_query(&axis, args.range, selected).aggregate(args.metric.aggregate());
+            let points = db.query(query).await?;
+            let (mut series, mut outages) = (Series::new(), Vec::new());
+            for point in points {
+                let x = axis.x(point.ts);
+                // 只有延迟为空才表示离线；旧数据可能没有 p95
+                if point.latency.is_none() {
+                    outages.push(x);
+                }
+                let value = args.metric.value(&point).unwrap_or(GAP);
+                series.entry(point.alias).or_default().push((x, value));
+            }
+            vec![Panel {
+                title: format!("过去 {} {}曲线", range, args.metric.title()),
+                y_label: format!("{} ({})", args.metric.title(), args.metric.unit()),
+                series,
+                fill: false,
+                outages,
+                y_max: None,
+                dim: [800.0, 500.0],
+            }]
+        }
+        // 上方延迟、下方丢包阴影，离线轮次在延迟图上单独标出
+        Metric::Loss => {
+            let points = db.query(chart_query(&axis, args.range, selected)).await?;
+            let (latency, loss, outages) = split_points(points, &axis);
+            vec![
+                Panel {
+                    title: format!("过去 {} 延迟与丢包", range),
+                    y_label: String::from("延迟 (ms)"),
+                    series: latency,
+                    fill: false,
+                    outages: outages.into_values().flatten().collect(),
+                    y_max: None,
+                    dim: [800.0, 400.0],
+                },
+                Panel {
+                    title: String::new(),
+                    y_label: String::from("丢包率 (%)"),
+                    series: loss,
+                    fill: true,
+                    outages: Vec::new(),
+                    y_max: Some(100.0),
+                    dim: [800.0, 300.0],
+                },
+            ]
+        }
+    };
+    if panels.iter().all(|p| p.series.is_empty() && p.outages.is_empty()) {
+        bot.send_message(chat_id, "所选时间范围内没有数据").await?;
+        return Ok(());
+    }
 
-    // 4.2 Build a small tick generator at 0, 30, 60 minutes:
-    let x_ticks = ticks::from_iter(vec![0.0, 30.0, 60.0].into_iter())
-        .with_tick_fmt(|&v| {
-            // 60 - v gives “minutes ago”
-            format!("{}m ago", (60.0 - v) as usize)
-        });
+    // 2. 构建 SVG 并在内存中栅格化
+    let png = task::spawn_blocking(move || render_grid(&[panels], &axis, 1)).await??;
 
-    // 1. 计算 rel_min ∈ [0,60]：0=1h 前，60=现在
-    let base_secs = since.timestamp() as f64;
-    let mut series_map: BTreeMap<String, Vec<(f64, f64)>> = BTreeMap::new();
-    for (alias, ts, lat, _loss) in rows {
-        let rel_min = (ts.timestamp() as f64 - base_secs) / 60.0;
-        series_map.entry(alias).or_default().push((rel_min, lat));
-    }
+    // 3. 直接发送内存中的图片
+    bot.send_photo(chat_id, InputFile::memory(png).file_name("graph.png"))
+        .await?;
 
+    Ok(())
+}
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ProbeKind;
 
-    let filename     = format!("graph_{}.svg", now.timestamp());
-    let filename_buf = PathBuf::from(&filename);
-    let series_owned = series_map.clone();
+    fn targets() -> Vec<TargetConfig> {
+        vec![
+            TargetConfig::new("hk", "1.2.3.4:443", ProbeKind::Tcp),
+            TargetConfig::new("jp", "5.6.7.8:443", ProbeKind::Tcp),
+        ]
+    }
 
-    // 2. 构建 SVG
-    let svg = task::spawn_blocking(move || -> anyhow::Result<String> {
-        let plots = series_owned.iter().map(|(alias, pts)| {
-            build::plot(alias.clone()).line(pts.clone().into_iter())
-        });
+    #[test]
+    fn defaults_match_usage() {
+        let args = parse_graph_args("", &targets()).unwrap();
+        assert!(USAGE.contains("range=1h"));
+        assert_eq!(args.range, Duration::hours(1));
+        assert_eq!(args.metric, Metric::Latency);
+        assert!(args.aliases.is_empty());
+    }
 
-        // —— 手动指定 X 轴刻度：0, 20, 40, 60 —— 
-        // 确保两个以上刻度，左端0代表“1h ago”，右端60代表“now”
-        let x_ticks = ticks::from_iter(vec![0.0, 20.0, 40.0, 60.0].into_iter())
-            .with_tick_fmt(|&v| {
-                // 0→"60m ago"，60→"0m ago"，20→"40m ago"…
-                format!("{}m ago", (60.0 - v) as usize)
-            });
-
-        // 仅在 Data 上注入自定义刻度
-        let data = frame_build()
-            .data(plots)
-            .map_xticks(|_| x_ticks);
-
-        let frame = data.build_and_label((
-            "过去 1 小时延迟曲线",
-            "时间 (minutes ago)",
-            "延迟 (ms)",
-        ));
-
-        let svg_text = frame.append_to(header().light_theme()).render_string()?;
-        fs::write(&filename_buf, svg_text.as_bytes())?;
-        Ok(filename)
-    })
-        .await??;
-
-    // 3. Rasterize & send exactly as you already do…
-    let img_filename = svg.replace(".svg", ".jpg");
-    let _ = convert_svg_to_image(&svg, &img_filename);
-    bot.send_photo(chat_id, InputFile::file(&img_filename)).await?;
+    #[test]
+    fn parses_targets_range_and_metric() {
+        let args = parse_graph_args("jp range=6h metric=p95", &targets()).unwrap();
+        assert_eq!(args.aliases, vec!["jp"]);
+        assert_eq!(args.range, Duration::hours(6));
+        assert_eq!(args.metric, Metric::P95);
 
-    Ok(())
+        let args = parse_graph_args("2d loss hk", &targets()).unwrap();
+        assert_eq!(args.aliases, vec!["hk"]);
+        assert_eq!(args.range, Duration::days(2));
+        assert_eq!(args.metric, Metric::Loss);
+    }
+
+    #[test]
+    fn rejects_unknown_arguments() {
+        assert!(parse_graph_args("sg", &targets()).is_err());
+        assert!(parse_graph_args("step=5m", &targets()).is_err());
+        assert!(parse_graph_args("range=90d", &targets()).is_err());
+    }
 }
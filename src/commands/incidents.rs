@@ -0,0 +1,88 @@
+use crate::alert::format_duration;
+use crate::commands::isonline::CmdResult;
+use crate::config::TargetConfig;
+use crate::db::Db;
+use chrono::{Duration, Local, Utc};
+use std::sync::Arc;
+use teloxide::prelude::Requester;
+use teloxide::types::ChatId;
+use teloxide::Bot;
+
+const USAGE: &str = "用法：/incidents [别名] [天数]，例如 /incidents hk1 30";
+/// 一次最多展示的故障条数，避免超出消息长度上限
+const MAX_SHOWN: usize = 30;
+
+/// Handle the `/incidents` command: recent outages with cause and duration
+pub async fn incidents_command(
+    bot: Bot,
+    chat_id: ChatId,
+    db: Arc<Db>,
+    targets: Vec<TargetConfig>,
+    args: &str,
+) -> CmdResult {
+    if !db.is_subscribed(chat_id.0).await.unwrap_or(false) {
+        return Ok(());
+    }
+
+    // 参数顺序不限：纯数字（可带 d）为天数，其余为别名
+    let (mut alias, mut days) = (None, 7);
+    for arg in args.split_whitespace() {
+        match arg.trim_end_matches(['d', 'D']).parse::<i64>() {
+            Ok(n) if n > 0 => days = n.min(3650),
+            _ if alias.is_none() => alias = Some(arg),
+            _ => {
+                bot.send_message(chat_id, USAGE).await?;
+                return Ok(());
+            }
+        }
+    }
+    if let Some(alias) = alias {
+        if !targets.iter().any(|t| t.alias == alias) {
+            bot.send_message(chat_id, format!("❌ 目标 {} 不存在", alias))
+                .await?;
+            return Ok(());
+        }
+    }
+
+    let now = Utc::now();
+    let incidents = match db.list_incidents(now - Duration::days(days), alias).await {
+        Ok(list) => list,
+        Err(e) => {
+            bot.send_message(chat_id, format!("❌ 读取故障记录失败: {}", e))
+                .await?;
+            return Ok(());
+        }
+    };
+    let incidents: Vec<_> = incidents
+        .into_iter()
+        .filter(|i| targets.iter().any(|t| t.alias == i.alias))
+        .collect();
+    if incidents.is_empty() {
+        bot.send_message(chat_id, format!("✔ 过去 {} 天没有故障", days))
+            .await?;
+        return Ok(());
+    }
+
+    let mut report = format!("📒 过去 {} 天共 {} 次故障：\n", days, incidents.len());
+    if incidents.len() > MAX_SHOWN {
+        report.push_str(&format!("（仅显示最近 {} 次）\n", MAX_SHOWN));
+    }
+    for i in incidents.iter().take(MAX_SHOWN) {
+        let duration = match i.end {
+            Some(end) => format!("持续 {}", format_duration(end - i.start)),
+            None => format!("进行中，已 {}", format_duration(now - i.start)),
+        };
+        report.push_str(&format!(
+            "\n{} [{}] {}\n    {}，原因 {}（{}），峰值丢包 {:.1}%\n",
+            if i.end.is_some() { "🟢" } else { "🔴" },
+            i.alias,
+            i.start.with_timezone(&Local).format("%Y-%m-%d %H:%M"),
+            duration,
+            i.cause,
+            i.reason,
+            i.peak_loss
+        ));
+    }
+    bot.send_message(chat_id, report).await?;
+    Ok(())
+}
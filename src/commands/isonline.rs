@@ -1,44 +1,26 @@
-use crate::config::Config;
+use crate::config::{Config, TargetConfig};
 use crate::db::Db;
-use chrono::{Duration, Local, Utc};
+use crate::probe;
+use chrono::Local;
 use futures::future::join_all;
-use std::{net::SocketAddr, sync::Arc};
+use std::sync::Arc;
 use teloxide::prelude::Requester;
 use teloxide::types::ChatId;
 use teloxide::{Bot, RequestError};
-use tokio::{
-    task,
-    time::{timeout, Duration as TokioDuration},
-};
+use tokio::sync::Semaphore;
+use tokio::task;
 
 /// Result type for command handlers
 pub type CmdResult = Result<(), RequestError>;
 
-/// Handle the `/start` command (admin only)
-
-/// Handle the `/stop` command (admin only)
-pub async fn stop_command(
-    bot: Bot,
-    chat_id: ChatId,
-    user_id: i64,
-    cfg: &Config,
-    db: Arc<Db>,
-) -> CmdResult {
-    if cfg.admins.contains(&user_id) {
-        db.remove_subscription(chat_id.0).await.ok();
-        bot.send_message(chat_id, "❌ 已取消订阅").await?;
-    }
-    Ok(())
-}
-
-/// Handle the `/isonline` command: live TCP probes
+/// Handle the `/isonline` command: live probes of every target
 pub async fn isonline_command(
     bot: Bot,
     chat_id: ChatId,
-    user_id: i64,
     cfg: &Config,
     db: Arc<Db>,
-    targets: Vec<(SocketAddr, String)>,
+    targets: Vec<TargetConfig>,
+    limiter: Arc<Semaphore>,
 ) -> CmdResult {
     if !db.is_subscribed(chat_id.0).await.unwrap_or(false) {
         return Ok(());
@@ -50,38 +32,24 @@ pub async fn isonline_command(
     let bot_clone = bot.clone();
     let cfg_clone = cfg.clone();
     let targets_clone = targets.clone();
-    let db_clone = db.clone();
 
     task::spawn(async move {
-        let probe_count = cfg_clone.probe_count;
         let mut probes = Vec::new();
 
-        for (addr, alias) in targets_clone {
-            let alias = alias.clone();
+        for target in targets_clone {
+            let alias = target.alias.clone();
+            let prober = probe::prober(&target, target.timeout(&cfg_clone));
+            let attempts = target.attempts(&cfg_clone);
+            let limiter = limiter.clone();
             probes.push(task::spawn(async move {
-                let mut latencies = Vec::new();
-                let mut fails = 0;
-                for _ in 0..probe_count {
-                    let start = std::time::Instant::now();
-                    let res = timeout(
-                        TokioDuration::from_secs(1),
-                        tokio::net::TcpStream::connect(&addr),
-                    )
-                    .await;
-                    match res {
-                        Ok(Ok(_)) => latencies.push(start.elapsed().as_millis() as u64),
-                        _ => fails += 1,
+                let result = match prober {
+                    Ok(prober) => {
+                        let _permit = limiter.acquire().await.expect("limiter closed");
+                        prober.probe(attempts).await
                     }
-                }
-                let total = probe_count as u64;
-                let success = total.saturating_sub(fails);
-                let avg = if !latencies.is_empty() {
-                    latencies.iter().sum::<u64>() / latencies.len() as u64
-                } else {
-                    0
+                    Err(e) => probe::failed_round(&alias, attempts, &e),
                 };
-                let loss = (fails as f64) / (total as f64) * 100.0;
-                (alias, success, total, avg, loss)
+                (alias, result)
             }));
         }
 
@@ -92,19 +60,33 @@ pub async fn isonline_command(
             "🟢 测试完成，完成时间：{}\n结果：\n",
             Local::now().format("%Y-%m-%d %H:%M:%S")
         );
-        for res in results {
-            if let Ok((alias, success, total, avg, loss)) = res {
-                let line = if success == total {
-                    format!("{}: ✔ 全部成功，平均延迟 {} ms\n", alias, avg)
-                } else if success == 0 {
-                    format!("{}: ❌ 全部失败\n", alias)
-                } else {
-                    format!(
-                        "{}: 部分成功，平均延迟 {} ms，丢包率 {:.1}%\n",
-                        alias, avg, loss
-                    )
-                };
-                report.push_str(&line);
+        for (alias, result) in results.into_iter().flatten() {
+            let (success, total) = (result.successes(), result.attempts.len());
+            let stats = result.stats();
+            let avg = stats.avg.unwrap_or(0.0);
+            let line = if success == total {
+                format!("{}: ✔ 全部成功，平均延迟 {:.1} ms\n", alias, avg)
+            } else if success == 0 {
+                match result.last_error() {
+                    Some(err) => format!("{}: ❌ 全部失败（{}）\n", alias, err.kind.label()),
+                    None => format!("{}: ❌ 全部失败\n", alias),
+                }
+            } else {
+                format!(
+                    "{}: 部分成功，平均延迟 {:.1} ms，丢包率 {:.1}%\n",
+                    alias, avg, stats.loss
+                )
+            };
+            report.push_str(&line);
+            if success > 0 {
+                report.push_str(&format!(
+                    "    min/p50/p95/max {:.1}/{:.1}/{:.1}/{:.1} ms，抖动 {:.1} ms\n",
+                    stats.min.unwrap_or(0.0),
+                    stats.p50.unwrap_or(0.0),
+                    stats.p95.unwrap_or(0.0),
+                    stats.max.unwrap_or(0.0),
+                    stats.jitter.unwrap_or(0.0)
+                ));
             }
         }
 
@@ -113,9 +95,3 @@ pub async fn isonline_command(
 
     Ok(())
 }
-
-/// Handle the `/graph` command (stub for now)
-pub async fn graph_command(bot: Bot, chat_id: ChatId, cfg: &Config, db: Arc<Db>) -> CmdResult {
-    // To be implemented: draw_graph & send
-    Ok(())
-}
@@ -0,0 +1,145 @@
+use crate::alert::parse_duration;
+use crate::commands::isonline::CmdResult;
+use crate::config::Config;
+use crate::db::Db;
+use crate::maintenance::MAX_WINDOW_DAYS;
+use crate::targets::Targets;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Duration, Local, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use std::sync::Arc;
+use teloxide::prelude::Requester;
+use teloxide::types::ChatId;
+use teloxide::Bot;
+
+const USAGE: &str = "用法：/maintenance <别名> <开始时间> <时长>\n\
+开始时间可以是 now、HH:MM 或 YYYY-MM-DD HH:MM（本地时间），例如 /maintenance hk1 03:00 30m";
+
+/// 解析维护开始时间；只给出 HH:MM 且今天已过时取明天
+fn parse_start(s: &str, now: DateTime<Utc>) -> Result<DateTime<Utc>> {
+    if s.eq_ignore_ascii_case("now") {
+        return Ok(now);
+    }
+    let local = if let Ok(time) = NaiveTime::parse_from_str(s, "%H:%M") {
+        let today = now.with_timezone(&Local).date_naive().and_time(time);
+        let start = Local
+            .from_local_datetime(&today)
+            .earliest()
+            .ok_or_else(|| anyhow!("无效时间: {}", s))?;
+        if start < now {
+            start + Duration::days(1)
+        } else {
+            start
+        }
+    } else {
+        let naive = NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M")
+            .or_else(|_| NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M"))
+            .map_err(|_| anyhow!("无效开始时间: {}", s))?;
+        Local
+            .from_local_datetime(&naive)
+            .earliest()
+            .ok_or_else(|| anyhow!("无效时间: {}", s))?
+    };
+    Ok(local.with_timezone(&Utc))
+}
+
+/// Handle the `/maintenance` command: list windows, or schedule a one-off window
+pub async fn maintenance_command(
+    bot: Bot,
+    chat_id: ChatId,
+    user_id: i64,
+    cfg: &Config,
+    db: Arc<Db>,
+    targets: Targets,
+    args: &str,
+) -> CmdResult {
+    if !cfg.admins.contains(&user_id) {
+        return Ok(());
+    }
+
+    let parts: Vec<&str> = args.split_whitespace().collect();
+    if parts.is_empty() {
+        return list_windows(bot, chat_id, db, targets).await;
+    }
+    let [alias, start @ .., duration] = parts.as_slice() else {
+        bot.send_message(chat_id, USAGE).await?;
+        return Ok(());
+    };
+    if start.is_empty() {
+        bot.send_message(chat_id, USAGE).await?;
+        return Ok(());
+    }
+    if !targets.contains(alias) {
+        bot.send_message(chat_id, format!("❌ 目标 {} 不存在", alias))
+            .await?;
+        return Ok(());
+    }
+    let window = parse_start(&start.join(" "), Utc::now()).and_then(|start| {
+        let length = parse_duration(duration)?;
+        if length > Duration::days(MAX_WINDOW_DAYS) {
+            return Err(anyhow!("维护时长需在 {}d 以内: {}", MAX_WINDOW_DAYS, duration));
+        }
+        let end = start
+            .checked_add_signed(length)
+            .ok_or_else(|| anyhow!("无效时长: {}", duration))?;
+        Ok((start, end))
+    });
+    let (start, end) = match window {
+        Ok(w) => w,
+        Err(e) => {
+            bot.send_message(chat_id, format!("❌ {}\n{}", e, USAGE)).await?;
+            return Ok(());
+        }
+    };
+
+    let text = match db.add_maintenance(alias, start, end).await {
+        Ok(()) => format!(
+            "🛠 已安排 {} 维护：{} 至 {}\n期间不告警、不计入可用率",
+            alias,
+            start.with_timezone(&Local).format("%Y-%m-%d %H:%M"),
+            end.with_timezone(&Local).format("%Y-%m-%d %H:%M")
+        ),
+        Err(e) => format!("❌ 保存维护窗口失败: {}", e),
+    };
+    bot.send_message(chat_id, text).await?;
+    Ok(())
+}
+
+/// 列出本会话可见目标的周期窗口与尚未结束的一次性窗口
+async fn list_windows(bot: Bot, chat_id: ChatId, db: Arc<Db>, targets: Targets) -> CmdResult {
+    let visible = match targets.visible_to(&db, chat_id.0).await {
+        Ok(visible) => visible,
+        Err(e) => {
+            bot.send_message(chat_id, format!("❌ 读取目标归属失败: {}", e)).await?;
+            return Ok(());
+        }
+    };
+    let mut report = String::from("🛠 维护窗口：\n");
+    for t in &visible {
+        for window in t.maintenance.iter().flatten() {
+            report.push_str(&format!(
+                "{}: 周期 {}，持续 {}\n",
+                t.alias, window.cron, window.duration
+            ));
+        }
+    }
+    match db.list_maintenance(Utc::now()).await {
+        Ok(list) => {
+            let list = list
+                .into_iter()
+                .filter(|(alias, _, _)| visible.iter().any(|t| &t.alias == alias));
+            for (alias, start, end) in list {
+                report.push_str(&format!(
+                    "{}: {} 至 {}\n",
+                    alias,
+                    start.with_timezone(&Local).format("%Y-%m-%d %H:%M"),
+                    end.with_timezone(&Local).format("%Y-%m-%d %H:%M")
+                ));
+            }
+        }
+        Err(e) => report.push_str(&format!("读取一次性维护窗口失败: {}\n", e)),
+    }
+    report.push('\n');
+    report.push_str(USAGE);
+    bot.send_message(chat_id, report).await?;
+    Ok(())
+}
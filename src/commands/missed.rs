@@ -0,0 +1,45 @@
+use crate::commands::isonline::CmdResult;
+use crate::db::Db;
+use chrono::Local;
+use std::sync::Arc;
+use teloxide::prelude::Requester;
+use teloxide::types::ChatId;
+use teloxide::Bot;
+
+/// 一次最多展示的告警条数，避免超出消息长度上限
+const MAX_SHOWN: usize = 20;
+
+/// Handle the `/missed` command: alerts suppressed while this chat was muted
+pub async fn missed_command(bot: Bot, chat_id: ChatId, db: Arc<Db>) -> CmdResult {
+    if !db.is_subscribed(chat_id.0).await.unwrap_or(false) {
+        return Ok(());
+    }
+
+    let missed = match db.take_missed_alerts(chat_id.0).await {
+        Ok(m) => m,
+        Err(e) => {
+            bot.send_message(chat_id, format!("❌ 读取告警日志失败: {}", e))
+                .await?;
+            return Ok(());
+        }
+    };
+    if missed.is_empty() {
+        bot.send_message(chat_id, "没有错过的告警").await?;
+        return Ok(());
+    }
+
+    let mut report = format!("📭 静音期间错过 {} 条告警：\n", missed.len());
+    let skipped = missed.len().saturating_sub(MAX_SHOWN);
+    if skipped > 0 {
+        report.push_str(&format!("（仅显示最近 {} 条）\n", MAX_SHOWN));
+    }
+    for (ts, text) in missed.iter().skip(skipped) {
+        report.push_str(&format!(
+            "\n[{}]\n{}\n",
+            ts.with_timezone(&Local).format("%m-%d %H:%M:%S"),
+            text
+        ));
+    }
+    bot.send_message(chat_id, report).await?;
+    Ok(())
+}
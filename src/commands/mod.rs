@@ -1,6 +1,19 @@
+pub mod add;
+pub mod alerts;
+pub mod assign;
+pub mod certs;
+pub mod dashboard;
+pub mod export;
 pub mod graph;
+pub mod incidents;
 pub mod isonline;
+pub mod maintenance;
+pub mod missed;
+pub mod mute;
+pub mod remove;
+pub mod sla;
 pub mod start;
 pub mod stop;
+pub mod targets;
 
-pub mod uptime;
\ No newline at end of file
+pub mod uptime;
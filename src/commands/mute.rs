@@ -0,0 +1,100 @@
+use crate::alert::{self, ALL_TARGETS};
+use crate::commands::isonline::CmdResult;
+use crate::config::Config;
+use crate::db::Db;
+use crate::targets::Targets;
+use chrono::{Duration, Local, Utc};
+use std::sync::Arc;
+use teloxide::prelude::Requester;
+use teloxide::types::ChatId;
+use teloxide::Bot;
+
+const USAGE: &str = "用法：/mute <别名|all> <时长>，例如 /mute all 2h";
+/// 单次静音的最长时长
+const MAX_MUTE_DAYS: i64 = 30;
+
+/// Handle the `/mute` command: silence alerts of one or all targets for a while
+pub async fn mute_command(
+    bot: Bot,
+    chat_id: ChatId,
+    user_id: i64,
+    cfg: &Config,
+    db: Arc<Db>,
+    targets: Targets,
+    args: &str,
+) -> CmdResult {
+    if !cfg.admins.contains(&user_id) {
+        return Ok(());
+    }
+
+    let parts: Vec<&str> = args.split_whitespace().collect();
+    let [alias, duration] = parts.as_slice() else {
+        bot.send_message(chat_id, USAGE).await?;
+        return Ok(());
+    };
+    let until = match alert::parse_duration(duration) {
+        Ok(d) if d > Duration::days(MAX_MUTE_DAYS) => None,
+        Ok(d) => Utc::now().checked_add_signed(d),
+        Err(e) => {
+            bot.send_message(chat_id, format!("❌ {}\n{}", e, USAGE)).await?;
+            return Ok(());
+        }
+    };
+    let Some(until) = until else {
+        bot.send_message(
+            chat_id,
+            format!("❌ 静音时长需在 {}d 以内: {}", MAX_MUTE_DAYS, duration),
+        )
+        .await?;
+        return Ok(());
+    };
+    let (key, name) = if alias.eq_ignore_ascii_case("all") {
+        (ALL_TARGETS, "全部目标")
+    } else if targets.contains(alias) {
+        (*alias, *alias)
+    } else {
+        bot.send_message(chat_id, format!("❌ 目标 {} 不存在", alias))
+            .await?;
+        return Ok(());
+    };
+
+    let text = match db.mute(chat_id.0, key, until).await {
+        Ok(()) => format!(
+            "🔕 已静音 {}，直到 {}\n期间的告警会被记录，可用 /missed 查看",
+            name,
+            until.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S")
+        ),
+        Err(e) => format!("❌ 静音失败: {}", e),
+    };
+    bot.send_message(chat_id, text).await?;
+    Ok(())
+}
+
+/// Handle the `/unmute` command: lift one or all mutes
+pub async fn unmute_command(
+    bot: Bot,
+    chat_id: ChatId,
+    user_id: i64,
+    cfg: &Config,
+    db: Arc<Db>,
+    args: &str,
+) -> CmdResult {
+    if !cfg.admins.contains(&user_id) {
+        return Ok(());
+    }
+
+    let alias = args.trim();
+    let alias = (!alias.is_empty() && !alias.eq_ignore_ascii_case("all")).then_some(alias);
+    let mut text = match db.unmute(chat_id.0, alias).await {
+        Ok(0) => String::from("当前没有对应的静音"),
+        Ok(_) => format!("🔔 已取消静音：{}", alias.unwrap_or("全部")),
+        Err(e) => format!("❌ 取消静音失败: {}", e),
+    };
+    if let Ok(missed) = db.count_missed_alerts(chat_id.0).await {
+        if missed > 0 {
+            text.push_str(&format!("\n静音期间错过 {} 条告警，可用 /missed 查看", missed));
+        }
+    }
+    bot.send_message(chat_id, text).await?;
+    Ok(())
+}
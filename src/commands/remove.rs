@@ -0,0 +1,54 @@
+use crate::commands::isonline::CmdResult;
+use crate::config::Config;
+use crate::db::Db;
+use crate::targets::Targets;
+use std::sync::Arc;
+use teloxide::prelude::Requester;
+use teloxide::types::ChatId;
+use teloxide::Bot;
+
+/// Handle the `/remove` command: stop monitoring a target added via `/add`
+pub async fn remove_command(
+    bot: Bot,
+    chat_id: ChatId,
+    user_id: i64,
+    cfg: &Config,
+    db: Arc<Db>,
+    targets: Targets,
+    alias: &str,
+) -> CmdResult {
+    if !cfg.admins.contains(&user_id) {
+        return Ok(());
+    }
+
+    let alias = alias.trim();
+    if alias.is_empty() {
+        bot.send_message(chat_id, "用法：/remove <别名>").await?;
+        return Ok(());
+    }
+    if targets.is_configured(alias) {
+        bot.send_message(
+            chat_id,
+            format!("❌ 目标 {} 来自 config.toml，请修改配置文件后重启", alias),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    match db.remove_target(alias).await {
+        Ok(removed) => {
+            if targets.remove(alias) || removed {
+                bot.send_message(chat_id, format!("✅ 已删除目标 {}", alias))
+                    .await?;
+            } else {
+                bot.send_message(chat_id, format!("❌ 目标 {} 不存在", alias))
+                    .await?;
+            }
+        }
+        Err(e) => {
+            bot.send_message(chat_id, format!("❌ 删除目标失败: {}", e))
+                .await?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,74 @@
+use crate::alert::parse_duration;
+use crate::commands::isonline::CmdResult;
+use crate::config::{Config, TargetConfig};
+use crate::db::Db;
+use crate::sla;
+use chrono::Utc;
+use std::sync::Arc;
+use teloxide::prelude::Requester;
+use teloxide::types::ChatId;
+use teloxide::Bot;
+
+const USAGE: &str = "用法：/sla [别名] [7d|30d|month|lastmonth]";
+
+/// Handle the `/sla` command: availability, downtime, MTTR and MTBF per target
+pub async fn sla_command(
+    bot: Bot,
+    chat_id: ChatId,
+    cfg: &Config,
+    db: Arc<Db>,
+    targets: Vec<TargetConfig>,
+    args: &str,
+) -> CmdResult {
+    if !db.is_subscribed(chat_id.0).await.unwrap_or(false) {
+        return Ok(());
+    }
+
+    // 参数顺序不限：能解析为区间的是区间，其余为别名
+    let now = Utc::now();
+    let (mut alias, mut period) = (None, None);
+    for arg in args.split_whitespace() {
+        match sla::parse_period(arg, now) {
+            Ok(p) if period.is_none() => period = Some(p),
+            // 形如时长但超出范围时报错，而不是当作别名
+            Err(e) if parse_duration(arg).is_ok() => {
+                bot.send_message(chat_id, format!("❌ {}\n{}", e, USAGE)).await?;
+                return Ok(());
+            }
+            _ if alias.is_none() => alias = Some(arg),
+            _ => {
+                bot.send_message(chat_id, USAGE).await?;
+                return Ok(());
+            }
+        }
+    }
+    let (since, until, label) = match period {
+        Some(p) => p,
+        None => match sla::parse_period("30d", now) {
+            Ok(p) => p,
+            Err(e) => {
+                bot.send_message(chat_id, format!("❌ {}", e)).await?;
+                return Ok(());
+            }
+        },
+    };
+    let selected: Vec<TargetConfig> = match alias {
+        Some(alias) => targets.into_iter().filter(|t| t.alias == alias).collect(),
+        None => targets,
+    };
+    if selected.is_empty() {
+        let text = match alias {
+            Some(alias) => format!("❌ 目标 {} 不存在", alias),
+            None => String::from("暂无监测目标"),
+        };
+        bot.send_message(chat_id, text).await?;
+        return Ok(());
+    }
+
+    let text = match sla::compute_all(&db, cfg, &selected, since, until).await {
+        Ok(reports) => sla::format_report(&format!("{}可用性（不含维护）", label), &reports),
+        Err(e) => format!("❌ 计算可用率失败: {}", e),
+    };
+    bot.send_message(chat_id, text).await?;
+    Ok(())
+}
@@ -4,7 +4,7 @@ use crate::db::Db;
 use std::sync::Arc;
 use teloxide::prelude::Requester;
 use teloxide::types::ChatId;
-use teloxide::{Bot, RequestError};
+use teloxide::Bot;
 
 pub async fn start_command(
     bot: Bot,
@@ -0,0 +1,70 @@
+use crate::commands::isonline::CmdResult;
+use crate::config::Config;
+use crate::db::Db;
+use crate::targets::Targets;
+use std::sync::Arc;
+use teloxide::prelude::Requester;
+use teloxide::types::ChatId;
+use teloxide::Bot;
+
+/// Handle the `/targets` command: list the targets visible to this chat
+///
+/// 只有在管理员的私聊中才列出全部目标及其会话分配，避免在群组中泄露其他会话的信息。
+pub async fn targets_command(
+    bot: Bot,
+    chat_id: ChatId,
+    user_id: i64,
+    private: bool,
+    cfg: &Config,
+    db: Arc<Db>,
+    targets: Targets,
+) -> CmdResult {
+    if !cfg.admins.contains(&user_id) {
+        return Ok(());
+    }
+
+    let list = if private {
+        targets.snapshot()
+    } else {
+        match targets.visible_to(&db, chat_id.0).await {
+            Ok(list) => list,
+            Err(e) => {
+                bot.send_message(chat_id, format!("❌ 读取目标归属失败: {}", e)).await?;
+                return Ok(());
+            }
+        }
+    };
+    if list.is_empty() {
+        bot.send_message(chat_id, "暂无监测目标").await?;
+        return Ok(());
+    }
+    let assignments = db.list_target_chats().await.unwrap_or_default();
+    let mut report = format!("📋 监测目标（共 {} 个）：\n", list.len());
+    for t in &list {
+        let source = if targets.is_configured(&t.alias) {
+            "配置文件"
+        } else {
+            "命令添加"
+        };
+        report.push_str(&format!(
+            "{}: {} {}（{}）\n",
+            t.alias,
+            t.kind.as_str(),
+            t.address,
+            source
+        ));
+        if !private {
+            continue;
+        }
+        let mut chats = t.chats.clone().unwrap_or_default();
+        chats.extend(assignments.get(&t.alias).into_iter().flatten());
+        chats.sort();
+        chats.dedup();
+        if !chats.is_empty() {
+            let chats: Vec<String> = chats.iter().map(|c| c.to_string()).collect();
+            report.push_str(&format!("    仅限会话：{}\n", chats.join(", ")));
+        }
+    }
+    bot.send_message(chat_id, report).await?;
+    Ok(())
+}
@@ -1,77 +1,123 @@
-use std::collections::BTreeMap;
 use std::sync::Arc;
-use chrono::{DateTime, Duration, TimeZone, Utc};
+use anyhow::anyhow;
+use chrono::{Duration, Utc};
 use teloxide::Bot;
 use teloxide::requests::Requester;
-use crate::db::Db;
+use crate::alert::{format_duration, parse_duration};
+use crate::commands::graph::parse_range;
+use crate::config::TargetConfig;
+use crate::db::{Db, MetricQuery};
+
+const USAGE: &str = "用法：/uptime [时间跨度] [每格时长]，例如 /uptime 24h 1h";
+/// 每个目标一行最多的格子数
+const MAX_BUCKETS: i64 = 48;
+
+/// 只给出时间跨度时，挑选不超过 24 格的最小整齐格长
+fn default_bucket(range: Duration) -> Duration {
+    [1, 5, 15, 30, 60, 120, 360, 720, 1440]
+        .into_iter()
+        .map(Duration::minutes)
+        .find(|b| range.num_seconds() <= b.num_seconds() * 24)
+        .unwrap_or(Duration::days(1))
+}
+
+/// 解析 `[时间跨度] [每格时长]`，默认过去 1 小时、每格 15 分钟
+fn parse_args(args: &str) -> anyhow::Result<(Duration, Duration)> {
+    let parts: Vec<&str> = args.split_whitespace().collect();
+    let (range, bucket) = match parts.as_slice() {
+        [] => (Duration::hours(1), Duration::minutes(15)),
+        [range] => {
+            let range = parse_range(range)?;
+            (range, default_bucket(range))
+        }
+        [range, bucket] => (parse_range(range)?, parse_duration(bucket)?),
+        _ => return Err(anyhow!("参数过多")),
+    };
+    if bucket < Duration::minutes(1) || bucket > range {
+        return Err(anyhow!("每格时长需在 1m 到时间跨度之间"));
+    }
+    let buckets = (range.num_seconds() + bucket.num_seconds() - 1) / bucket.num_seconds();
+    if buckets > MAX_BUCKETS {
+        return Err(anyhow!("格子过多（{} 格），最多 {} 格，请增大每格时长", buckets, MAX_BUCKETS));
+    }
+    Ok((range, bucket))
+}
+
+/// 一格的状态：无数据、整格维护，或非维护轮次的平均丢包率
+#[derive(Default, Clone, Copy)]
+enum Bucket {
+    #[default]
+    Empty,
+    Maintenance,
+    Loss(f64),
+}
+
+impl Bucket {
+    fn status(&self) -> &'static str {
+        let avg = match *self {
+            Bucket::Empty => return "⬜",
+            Bucket::Maintenance => return "🟦",
+            Bucket::Loss(avg) => avg,
+        };
+        if avg < 5.0 {
+            "🟩"
+        } else if avg < 50.0 {
+            "🟨"
+        } else {
+            "🟥"
+        }
+    }
+}
 
 pub async fn draw_uptime (
     bot: Bot,
     chat_id: teloxide::types::ChatId,
     db: Arc<Db>,
+    targets: Vec<TargetConfig>,
+    args: &str,
 ) -> anyhow::Result<()> {
-    let lookback = 1;
-    let now =  Utc::now();
-    let since = now - Duration::hours(lookback);
-    
-    let metrics = db.query_metrics(since).await?;
-
-    // 2. 用嵌套的 BTreeMap 存桶：alias -> (window_start -> total_loss)
-    let mut alias_buckets: BTreeMap<String, BTreeMap<DateTime<Utc>, f64>> = BTreeMap::new();
-
-    for (alias, ts, _latency, loss) in metrics {
-        // 计算这个时间点对应的 15 分钟窗口起点
-        let secs = ts.timestamp();
-        let window_start = secs - (secs % (15 * 60));
-        let bucket_ts = Utc.timestamp_opt(window_start, 0).single().unwrap();
+    let (range, bucket) = match parse_args(args) {
+        Ok(v) => v,
+        Err(e) => {
+            bot.send_message(chat_id, format!("❌ {}\n{}", e, USAGE)).await?;
+            return Ok(());
+        }
+    };
+    let now = Utc::now();
+    let since = now - range;
+    let n = ((range.num_seconds() + bucket.num_seconds() - 1) / bucket.num_seconds()) as usize;
 
-        // 累加到对应 alias + 窗口
-        alias_buckets
-            .entry(alias.clone())
-            .or_default()
-            .entry(bucket_ts)
-            .and_modify(|sum| *sum += loss)
-            .or_insert(loss);
+    // 在 SQL 中按格汇总，格子从 `since` 起对齐，空格显示为无数据
+    let query = MetricQuery::new(since, now)
+        .aliases(targets.iter().map(|t| t.alias.as_str()))
+        .bucket(bucket);
+    let points = db.query(query).await?;
+    let mut rows: Vec<(String, Vec<Bucket>)> = targets
+        .iter()
+        .map(|t| (t.alias.clone(), vec![Bucket::default(); n]))
+        .collect();
+    for point in points {
+        let Some((_, buckets)) = rows.iter_mut().find(|(a, _)| *a == point.alias) else {
+            continue;
+        };
+        let idx = ((point.ts - since).num_seconds() / bucket.num_seconds()).clamp(0, n as i64 - 1);
+        buckets[idx as usize] = if point.maintenance() {
+            Bucket::Maintenance
+        } else {
+            Bucket::Loss(point.loss)
+        };
     }
 
-    // // 3. 对每个 alias、每个窗口计算状态并输出
-    // for (alias, buckets) in alias_buckets {
-    //     println!("=== alias: {} ===", alias);
-    //     for (window_start, total_loss) in buckets {
-    //         let status = if (total_loss - 100.0).abs() < std::f64::EPSILON {
-    //             "== 100%"
-    //         } else if total_loss > 50.0 {
-    //             "> 50%"
-    //         } else {
-    //             "< 50%"
-    //         };
-    //         println!(
-    //             "[{} - {}): loss_sum = {:.2}%, status = {}",
-    //             window_start.format("%Y-%m-%d %H:%M"),
-    //             (window_start + Duration::minutes(15)).format("%H:%M"),
-    //             total_loss,
-    //             status
-    //         );
-    //     }
-    //     println!();
-    // }
-    
-    let mut message_to_send: String = format!("过去{} 小时延迟\n", lookback);
-    for (alias, buckets) in alias_buckets {
-        let mut line_text: String = format!("[{}]: ", alias);
-        for(_window_start, total_loss) in buckets {
-            let status = if total_loss <= (50.0 - f64::EPSILON) {
-                "🟩"
-            } else if total_loss > (50.0 - f64::EPSILON) && total_loss < (100.0 - f64::EPSILON) {
-                "🟨"
-            } else {
-                "🟥"
-            };
-            line_text.push_str(&status);
-        }
-        line_text.push('\n');
-        message_to_send.push_str(&line_text);
+    let mut message_to_send = format!(
+        "过去 {} 在线状态（每格 {}）\n",
+        format_duration(range),
+        format_duration(bucket)
+    );
+    for (alias, buckets) in rows {
+        let line: String = buckets.iter().map(Bucket::status).collect();
+        message_to_send.push_str(&format!("[{}]: {}\n", alias, line));
     }
-    let _ = bot.send_message(chat_id, message_to_send).await;
+    message_to_send.push_str("🟩 正常 🟨 部分丢包 🟥 严重丢包 🟦 维护中 ⬜ 无数据\n");
+    bot.send_message(chat_id, message_to_send).await?;
     Ok(())
-}
\ No newline at end of file
+}
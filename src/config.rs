@@ -1,21 +1,140 @@
-use anyhow::Result;
+use crate::maintenance::MaintenanceWindow;
+use crate::probe;
+use anyhow::{anyhow, Context, Result};
 use serde::Deserialize;
 use std::fs;
+use std::str::FromStr;
+use std::time::Duration;
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct Config {
     pub token: String,
     pub log_level: Option<String>,
+    #[allow(dead_code)]
     pub socks5_proxy: Option<String>,
     pub admins: Vec<i64>,
     pub targets: Vec<TargetConfig>,
-    pub probe_count: usize,
+    /// 旧配置项，等同于 `attempts`
+    pub probe_count: Option<usize>,
+    /// 默认探测间隔 (秒)，默认 60
+    pub interval: Option<u64>,
+    /// 默认单次探测超时 (毫秒)，默认按探测类型而定
+    pub timeout: Option<u64>,
+    /// 默认每轮探测次数，默认 5
+    pub attempts: Option<usize>,
+    /// 同时进行探测的目标数上限，默认 16
+    pub concurrency: Option<usize>,
+    /// 每月初推送上个月的可用性报告，默认开启
+    pub monthly_report: Option<bool>,
+    /// metrics 的保留与降采样策略
+    pub retention: Option<RetentionConfig>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+/// metrics 保留策略，时长格式同 `/mute`（如 "7d"），`forever` 表示永久保留
+///
+/// ```toml
+/// [retention]
+/// raw = "7d"
+/// rollup_5m = "90d"
+/// rollup_1h = "forever"
+/// ```
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct RetentionConfig {
+    /// 原始数据保留时长，默认 7d，至少 1d
+    pub raw: Option<String>,
+    /// 5 分钟汇总保留时长，默认 90d
+    pub rollup_5m: Option<String>,
+    /// 1 小时汇总保留时长，默认永久
+    pub rollup_1h: Option<String>,
+}
+
+/// 探测类型
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ProbeKind {
+    /// TCP 建连
+    #[default]
+    Tcp,
+    /// HTTP(S) 请求，`address` 为完整 URL
+    Http,
+    /// TLS 握手并检查证书有效期，`address` 为 host:port
+    Tls,
+    /// DNS 查询，`address` 为要解析的域名
+    Dns,
+    /// UDP 请求/应答，`address` 为 host:port
+    Udp,
+}
+
+#[derive(Debug, Deserialize, Clone, Default, PartialEq)]
 pub struct TargetConfig {
     pub address: String,
     pub alias: String,
+    #[serde(default)]
+    pub kind: ProbeKind,
+    /// HTTP：期望的状态码，默认任意 2xx/3xx
+    pub expect_status: Option<Vec<u16>>,
+    /// HTTP：响应体需匹配的正则
+    pub body_regex: Option<String>,
+    /// TLS：握手使用的 SNI，默认取 `address` 中的主机名
+    pub sni: Option<String>,
+    /// TLS：证书剩余天数不足该值时告警，默认 14
+    pub cert_warn_days: Option<i64>,
+    /// DNS：使用的解析器 (ip 或 ip:port)，默认系统解析器
+    pub resolver: Option<String>,
+    /// DNS：查询的记录类型，默认 A
+    pub record: Option<String>,
+    /// UDP：发送的文本负载
+    pub payload: Option<String>,
+    /// UDP：发送的十六进制负载，优先于 `payload`
+    pub payload_hex: Option<String>,
+    /// UDP：应答需匹配的正则（按字节匹配），默认任意应答
+    pub expect: Option<String>,
+    /// 探测间隔 (秒)，覆盖全局 `interval`
+    pub interval: Option<u64>,
+    /// 单次探测超时 (毫秒)，覆盖全局 `timeout`
+    pub timeout: Option<u64>,
+    /// 每轮探测次数，覆盖全局 `attempts`
+    pub attempts: Option<usize>,
+    /// 丢包率 (%) 达到该值视为异常，默认 100
+    pub loss_threshold: Option<f64>,
+    /// 平均延迟 (ms) 超过该值视为异常，默认不检查
+    pub latency_threshold: Option<f64>,
+    /// 连续异常多少轮后告警，默认 1
+    pub fail_rounds: Option<u32>,
+    /// 连续正常多少轮后恢复，默认 1
+    pub recover_rounds: Option<u32>,
+    /// 可见该目标的会话；与 `/assign` 的分配合并，均未设置时所有会话可见
+    pub chats: Option<Vec<i64>>,
+    /// 周期性维护窗口，期间照常记录但不告警、不计入可用率
+    pub maintenance: Option<Vec<MaintenanceWindow>>,
+}
+
+impl ProbeKind {
+    /// 配置文件与数据库中使用的名称
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ProbeKind::Tcp => "tcp",
+            ProbeKind::Http => "http",
+            ProbeKind::Tls => "tls",
+            ProbeKind::Dns => "dns",
+            ProbeKind::Udp => "udp",
+        }
+    }
+}
+
+impl FromStr for ProbeKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "tcp" => Ok(ProbeKind::Tcp),
+            "http" => Ok(ProbeKind::Http),
+            "tls" => Ok(ProbeKind::Tls),
+            "dns" => Ok(ProbeKind::Dns),
+            "udp" => Ok(ProbeKind::Udp),
+            _ => Err(anyhow!("未知探测类型: {}（可选 tcp/http/tls/dns/udp）", s)),
+        }
+    }
 }
 
 impl Config {
@@ -23,6 +142,11 @@ impl Config {
     pub fn load(path: &str) -> Result<Self> {
         let s = fs::read_to_string(path)?;
         let cfg: Config = toml::from_str(&s)?;
+        cfg.retention().validate().context("retention 配置无效")?;
+        for target in &cfg.targets {
+            probe::validate(target)
+                .with_context(|| format!("目标 {} 配置无效", target.alias))?;
+        }
         Ok(cfg)
     }
 
@@ -30,4 +154,143 @@ impl Config {
     pub fn log_level(&self) -> &str {
         self.log_level.as_deref().unwrap_or("info")
     }
+
+    /// 同时探测的目标数上限（默认 16）
+    pub fn concurrency(&self) -> usize {
+        self.concurrency.unwrap_or(16).max(1)
+    }
+
+    /// 是否推送月报（默认开启）
+    pub fn monthly_report(&self) -> bool {
+        self.monthly_report.unwrap_or(true)
+    }
+
+    /// metrics 保留策略（未配置时全部取默认值）
+    pub fn retention(&self) -> RetentionConfig {
+        self.retention.clone().unwrap_or_default()
+    }
+}
+
+impl RetentionConfig {
+    /// 检查各项时长，原始数据至少保留 1 天，保证压缩时能看到完整的小时
+    pub fn validate(&self) -> Result<()> {
+        let raw = parse_keep(self.raw.as_deref(), Some("7d"))?;
+        parse_keep(self.rollup_5m.as_deref(), Some("90d"))?;
+        parse_keep(self.rollup_1h.as_deref(), None)?;
+        match raw {
+            Some(raw) if raw < chrono::Duration::days(1) => Err(anyhow!("raw 至少为 1d")),
+            _ => Ok(()),
+        }
+    }
+
+    /// 原始数据保留时长（默认 7 天），`None` 表示永久
+    pub fn raw(&self) -> Option<chrono::Duration> {
+        parse_keep(self.raw.as_deref(), Some("7d")).unwrap_or(Some(chrono::Duration::days(7)))
+    }
+
+    /// 5 分钟汇总保留时长（默认 90 天），`None` 表示永久
+    pub fn rollup_5m(&self) -> Option<chrono::Duration> {
+        parse_keep(self.rollup_5m.as_deref(), Some("90d")).unwrap_or(Some(chrono::Duration::days(90)))
+    }
+
+    /// 1 小时汇总保留时长（默认永久）
+    pub fn rollup_1h(&self) -> Option<chrono::Duration> {
+        parse_keep(self.rollup_1h.as_deref(), None).unwrap_or(None)
+    }
+}
+
+/// 保留时长上限，更长请写 `forever`
+const MAX_KEEP_DAYS: i64 = 36500;
+
+/// 解析保留时长，`forever` 为 `None`
+fn parse_keep(value: Option<&str>, default: Option<&str>) -> Result<Option<chrono::Duration>> {
+    match value.or(default) {
+        None => Ok(None),
+        Some(s) if s.eq_ignore_ascii_case("forever") => Ok(None),
+        Some(s) => {
+            let keep = crate::alert::parse_duration(s)?;
+            if keep > chrono::Duration::days(MAX_KEEP_DAYS) {
+                return Err(anyhow!("保留时长需在 {}d 以内，更长请使用 forever: {}", MAX_KEEP_DAYS, s));
+            }
+            Ok(Some(keep))
+        }
+    }
+}
+
+impl TargetConfig {
+    /// 通过命令添加的目标：仅有别名、地址和类型，其余取默认值
+    pub fn new(alias: &str, address: &str, kind: ProbeKind) -> Self {
+        TargetConfig {
+            alias: alias.to_string(),
+            address: address.to_string(),
+            kind,
+            ..Default::default()
+        }
+    }
+
+    /// 探测间隔：目标配置 > 全局配置 > 60 秒
+    pub fn interval(&self, cfg: &Config) -> Duration {
+        Duration::from_secs(self.interval.or(cfg.interval).unwrap_or(60).max(1))
+    }
+
+    /// 单次探测超时：目标配置 > 全局配置 > 探测类型默认值
+    pub fn timeout(&self, cfg: &Config) -> Duration {
+        self.timeout
+            .or(cfg.timeout)
+            .map(Duration::from_millis)
+            .unwrap_or_else(|| probe::default_timeout(self.kind))
+    }
+
+    /// 每轮探测次数：目标配置 > 全局 `attempts` > `probe_count` > 5
+    pub fn attempts(&self, cfg: &Config) -> usize {
+        self.attempts
+            .or(cfg.attempts)
+            .or(cfg.probe_count)
+            .unwrap_or(5)
+            .max(1)
+    }
+
+    /// 丢包率阈值（默认 100%）
+    pub fn loss_threshold(&self) -> f64 {
+        self.loss_threshold.unwrap_or(100.0)
+    }
+
+    /// 连续异常轮数（默认 1）
+    pub fn fail_rounds(&self) -> u32 {
+        self.fail_rounds.unwrap_or(1).max(1)
+    }
+
+    /// 连续恢复轮数（默认 1）
+    pub fn recover_rounds(&self) -> u32 {
+        self.recover_rounds.unwrap_or(1).max(1)
+    }
+
+    /// 证书到期预警天数（默认 14）
+    pub fn cert_warn_days(&self) -> i64 {
+        self.cert_warn_days.unwrap_or(14)
+    }
+
+    /// DNS 记录类型（默认 A）
+    pub fn record(&self) -> &str {
+        self.record.as_deref().unwrap_or("A")
+    }
+
+    /// 会话能否看到该目标，`assigned` 为通过 `/assign` 分配的会话
+    pub fn visible_to(&self, chat_id: i64, assigned: &[i64]) -> bool {
+        let configured = self.chats.as_deref().unwrap_or_default();
+        (configured.is_empty() && assigned.is_empty())
+            || configured.contains(&chat_id)
+            || assigned.contains(&chat_id)
+    }
+
+    /// 按阈值评估一轮探测结果，异常时返回原因
+    pub fn evaluate(&self, avg: f64, loss: f64) -> Option<String> {
+        if loss >= self.loss_threshold() {
+            return Some(format!("丢包率 {:.1}%", loss));
+        }
+        match self.latency_threshold {
+            Some(max) if avg > max => Some(format!("平均延迟 {:.1} ms > {} ms", avg, max)),
+            _ => None,
+        }
+    }
 }
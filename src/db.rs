@@ -1,131 +1,935 @@
 // src/db.rs
-use chrono::{DateTime, Utc};
+mod conn;
+mod migrations;
+mod query;
+
+use conn::{Readers, Writer};
+pub use query::{Aggregate, MetricPoint, MetricQuery, MetricRecord};
+
+use crate::alert::{ChatSettings, Severity};
+use crate::config::{ProbeKind, TargetConfig};
+use crate::probe::{PhaseTimings, RoundStats};
+use chrono::{DateTime, Duration, Utc};
 use rusqlite::{ffi, params, Connection, Error, ErrorCode, Result};
+use std::collections::HashMap;
 use std::sync::Arc;
-use tokio::sync::Mutex;
 
-/// 数据库客户端，内部持有一个异步互斥的 rusqlite::Connection
+/// 一个汇总桶中用于计算可用率的轮数统计
+///
+/// 延迟的 min/avg/max 与丢包率另存于汇总表中，通过 `Db::query` 按精度读取。
+#[derive(Debug, Clone)]
+pub struct Rollup {
+    pub alias: String,
+    pub ts: DateTime<Utc>,
+    /// 桶内的轮数
+    pub rounds: u32,
+    /// 其中丢包率达到目标阈值的轮数（不含维护中的轮次）
+    pub down_rounds: u32,
+    /// 其中处于维护窗口的轮数
+    pub maintenance_rounds: u32,
+}
+
+/// metrics 的存储精度
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    /// 每轮一条的原始数据
+    Raw,
+    FiveMinutes,
+    Hourly,
+}
+
+impl Resolution {
+    pub const ALL: [Resolution; 3] = [Resolution::Raw, Resolution::FiveMinutes, Resolution::Hourly];
+
+    fn table(self) -> &'static str {
+        match self {
+            Resolution::Raw => "metrics",
+            Resolution::FiveMinutes => "metrics_5m",
+            Resolution::Hourly => "metrics_1h",
+        }
+    }
+
+    /// 每个汇总桶的长度，原始数据为 `None`
+    pub fn step(self) -> Option<Duration> {
+        match self {
+            Resolution::Raw => None,
+            Resolution::FiveMinutes => Some(Duration::minutes(5)),
+            Resolution::Hourly => Some(Duration::hours(1)),
+        }
+    }
+
+    /// 查询跨度不超过该值时才使用此精度，避免点数过多
+    fn max_range(self) -> Duration {
+        match self {
+            Resolution::Raw => Duration::days(2),
+            Resolution::FiveMinutes => Duration::days(14),
+            Resolution::Hourly => Duration::MAX,
+        }
+    }
+
+    /// 汇总进度（已汇总到的时间）在 `bot_state` 中的键，原始数据为 `None`
+    pub fn state_key(self) -> Option<&'static str> {
+        match self {
+            Resolution::Raw => None,
+            Resolution::FiveMinutes => Some("rollup_5m_until"),
+            Resolution::Hourly => Some("rollup_1h_until"),
+        }
+    }
+}
+
+/// 一次故障记录
+#[derive(Debug, Clone)]
+pub struct Incident {
+    pub alias: String,
+    pub start: DateTime<Utc>,
+    /// 尚未恢复时为 `None`
+    pub end: Option<DateTime<Utc>>,
+    /// 故障类型，取 `ErrorKind::as_str()`，仅因延迟超限时为 "latency"
+    pub cause: String,
+    /// 告警中的原因描述
+    pub reason: String,
+    /// 故障期间的最高丢包率 (%)
+    pub peak_loss: f64,
+}
+
+/// 一轮探测要写入的全部数据，由 `Db::record_round` 在一个事务中提交
+#[derive(Debug, Clone)]
+pub struct RoundRecord {
+    pub alias: String,
+    pub ts: DateTime<Utc>,
+    pub stats: RoundStats,
+    pub timings: PhaseTimings,
+    pub maintenance: bool,
+    /// TLS 目标本轮看到的证书到期时间
+    pub not_after: Option<DateTime<Utc>>,
+    pub incident: Option<IncidentUpdate>,
+}
+
+/// 本轮对故障记录的改动
+#[derive(Debug, Clone)]
+pub enum IncidentUpdate {
+    /// 新建一条进行中的故障
+    Open {
+        start: DateTime<Utc>,
+        cause: String,
+        reason: String,
+        loss: f64,
+    },
+    /// 故障期间每轮异常都更新一次峰值丢包率
+    Peak { id: i64, loss: f64 },
+    /// 故障恢复
+    Close { id: i64, end: DateTime<Utc> },
+}
+
+/// 数据库客户端
+///
+/// 写操作在独占写连接的后台线程上串行执行，读操作使用只读连接池，
+/// 所有方法都不会阻塞异步运行时。
 #[derive(Clone)]
 pub struct Db {
-    conn: Arc<Mutex<Connection>>,
+    writer: Arc<Writer>,
+    readers: Arc<Readers>,
 }
 
 impl Db {
-    /// 打开数据库并初始化表结构
+    /// 打开数据库并把表结构迁移到最新版本
     pub async fn new(path: &str) -> Result<Self> {
-        // 由于 rusqlite::Connection!Send，必须在同步上下文打开
-        let conn = Connection::open(path)?;
-        conn.execute_batch(
-            r#"
-            CREATE TABLE IF NOT EXISTS subscriptions (
-                chat_id   INTEGER PRIMARY KEY
-            );
-            CREATE TABLE IF NOT EXISTS metrics (
-                id        INTEGER PRIMARY KEY AUTOINCREMENT,
-                alias     TEXT    NOT NULL,
-                ts        DATETIME NOT NULL,
-                latency   REAL    NOT NULL,
-                loss_rate REAL    NOT NULL
-            );
-            CREATE INDEX IF NOT EXISTS idx_metrics_ts_alias
-                ON metrics(ts, alias);
-        "#,
-        )?;
+        let owned = path.to_owned();
+        let conn = tokio::task::spawn_blocking(move || {
+            let mut conn = Connection::open(&owned)?;
+            conn::configure_writer(&conn)?;
+            migrations::migrate(&mut conn)?;
+            Ok::<_, Error>(conn)
+        })
+        .await
+        .map_err(|e| {
+            Error::SqliteFailure(
+                ffi::Error::new(ErrorCode::Unknown as i32),
+                Some(format!("JoinError: {}", e)),
+            )
+        })??;
         Ok(Db {
-            conn: Arc::new(Mutex::new(conn)),
+            writer: Arc::new(Writer::spawn(conn)?),
+            readers: Readers::new(path),
         })
     }
 
+    /// 在写线程上执行写操作
+    async fn write<T, F>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&mut Connection) -> Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        self.writer.call(f).await
+    }
+
+    /// 用只读连接执行查询
+    async fn read<T, F>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&Connection) -> Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        self.readers.call(f).await
+    }
+
     /// 添加订阅
     pub async fn add_subscription(&self, chat_id: i64) -> Result<()> {
-        let mut c = self.conn.lock().await;
-        c.execute(
-            "INSERT OR IGNORE INTO subscriptions(chat_id) VALUES(?1)",
-            params![chat_id],
-        )?;
-        Ok(())
+        self.write(move |c| {
+            c.execute(
+                "INSERT OR IGNORE INTO subscriptions(chat_id) VALUES(?1)",
+                params![chat_id],
+            )?;
+            Ok(())
+        })
+        .await
     }
 
     /// 取消订阅
     pub async fn remove_subscription(&self, chat_id: i64) -> Result<()> {
-        let mut c = self.conn.lock().await;
-        c.execute(
-            "DELETE FROM subscriptions WHERE chat_id=?1",
-            params![chat_id],
-        )?;
-        Ok(())
+        self.write(move |c| {
+            c.execute(
+                "DELETE FROM subscriptions WHERE chat_id=?1",
+                params![chat_id],
+            )?;
+            Ok(())
+        })
+        .await
     }
 
     /// 检查是否已订阅
     pub async fn is_subscribed(&self, chat_id: i64) -> Result<bool> {
-        let c = self.conn.lock().await;
-        let exists: i32 = c.query_row(
-            "SELECT EXISTS(SELECT 1 FROM subscriptions WHERE chat_id=?1)",
-            params![chat_id],
-            |r| r.get(0),
-        )?;
-        Ok(exists != 0)
+        self.read(move |c| {
+            let exists: i32 = c.query_row(
+                "SELECT EXISTS(SELECT 1 FROM subscriptions WHERE chat_id=?1)",
+                params![chat_id],
+                |r| r.get(0),
+            )?;
+            Ok(exists != 0)
+        })
+        .await
+    }
+
+    /// 列出所有订阅的会话
+    pub async fn list_subscriptions(&self) -> Result<Vec<i64>> {
+        self.read(|c| {
+            let mut stmt = c.prepare("SELECT chat_id FROM subscriptions")?;
+            let rows = stmt.query_map([], |r| r.get(0))?;
+            rows.collect()
+        })
+        .await
+    }
+
+    /// 保存通过命令添加的目标，别名已存在时返回 false
+    pub async fn add_target(&self, target: &TargetConfig) -> Result<bool> {
+        let (alias, address, kind) = (target.alias.clone(), target.address.clone(), target.kind);
+        self.write(move |c| {
+            let n = c.execute(
+                "INSERT OR IGNORE INTO targets(alias, address, kind, created_at) VALUES(?1,?2,?3,?4)",
+                params![alias, address, kind.as_str(), Utc::now().naive_utc()],
+            )?;
+            Ok(n > 0)
+        })
+        .await
+    }
+
+    /// 删除通过命令添加的目标及其会话分配、维护窗口，不存在时返回 false
+    pub async fn remove_target(&self, alias: &str) -> Result<bool> {
+        let alias = alias.to_owned();
+        self.write(move |c| {
+            let tx = c.transaction()?;
+            tx.execute("DELETE FROM target_chats WHERE alias=?1", params![alias])?;
+            tx.execute("DELETE FROM maintenance WHERE alias=?1", params![alias])?;
+            tx.execute(
+                "UPDATE incidents SET end_ts=?2 WHERE alias=?1 AND end_ts IS NULL",
+                params![alias, Utc::now().naive_utc()],
+            )?;
+            let n = tx.execute("DELETE FROM targets WHERE alias=?1", params![alias])?;
+            tx.commit()?;
+            Ok(n > 0)
+        })
+        .await
+    }
+
+    /// 列出所有通过命令添加的目标，类型无法识别的记录会被跳过
+    pub async fn list_targets(&self) -> Result<Vec<TargetConfig>> {
+        self.read(|c| {
+            let mut stmt =
+                c.prepare("SELECT alias, address, kind FROM targets ORDER BY created_at")?;
+            let rows = stmt.query_map([], |r| {
+                Ok((r.get::<_, String>(0)?, r.get::<_, String>(1)?, r.get::<_, String>(2)?))
+            })?;
+            let mut targets = Vec::new();
+            for row in rows {
+                let (alias, address, kind) = row?;
+                match kind.parse::<ProbeKind>() {
+                    Ok(kind) => targets.push(TargetConfig::new(&alias, &address, kind)),
+                    Err(e) => log::warn!("忽略目标 [{}]: {}", alias, e),
+                }
+            }
+            Ok(targets)
+        })
+        .await
+    }
+
+    /// 把目标分配给会话，已分配时返回 false
+    pub async fn assign_target(&self, alias: &str, chat_id: i64) -> Result<bool> {
+        let alias = alias.to_owned();
+        self.write(move |c| {
+            let n = c.execute(
+                "INSERT OR IGNORE INTO target_chats(alias, chat_id) VALUES(?1,?2)",
+                params![alias, chat_id],
+            )?;
+            Ok(n > 0)
+        })
+        .await
+    }
+
+    /// 取消目标对会话的分配，未分配时返回 false
+    pub async fn unassign_target(&self, alias: &str, chat_id: i64) -> Result<bool> {
+        let alias = alias.to_owned();
+        self.write(move |c| {
+            let n = c.execute(
+                "DELETE FROM target_chats WHERE alias=?1 AND chat_id=?2",
+                params![alias, chat_id],
+            )?;
+            Ok(n > 0)
+        })
+        .await
+    }
+
+    /// 某个目标被分配到的会话
+    pub async fn target_chats(&self, alias: &str) -> Result<Vec<i64>> {
+        let alias = alias.to_owned();
+        self.read(move |c| {
+            let mut stmt = c.prepare("SELECT chat_id FROM target_chats WHERE alias=?1")?;
+            let rows = stmt.query_map(params![alias], |r| r.get(0))?;
+            rows.collect()
+        })
+        .await
+    }
+
+    /// 所有目标的会话分配：alias -> chat_ids
+    pub async fn list_target_chats(&self) -> Result<HashMap<String, Vec<i64>>> {
+        self.read(|c| {
+            let mut stmt = c.prepare("SELECT alias, chat_id FROM target_chats")?;
+            let rows =
+                stmt.query_map([], |r| Ok((r.get::<_, String>(0)?, r.get::<_, i64>(1)?)))?;
+            let mut map: HashMap<String, Vec<i64>> = HashMap::new();
+            for row in rows {
+                let (alias, chat_id) = row?;
+                map.entry(alias).or_default().push(chat_id);
+            }
+            Ok(map)
+        })
+        .await
     }
 
-    /// 插入一次探测结果
-    pub async fn insert_metric(
+    /// 读取会话的告警偏好，只包含尚未到期的静音
+    pub async fn chat_settings(&self, chat_id: i64) -> Result<ChatSettings> {
+        self.read(move |c| {
+            let severities: Option<String> = match c.query_row(
+                "SELECT severities FROM chat_settings WHERE chat_id=?1",
+                params![chat_id],
+                |r| r.get(0),
+            ) {
+                Ok(v) => v,
+                Err(Error::QueryReturnedNoRows) => None,
+                Err(e) => return Err(e),
+            };
+            let severities = severities.map(|s| {
+                s.split(',')
+                    .filter_map(|name| name.parse::<Severity>().ok())
+                    .collect()
+            });
+            let mut stmt =
+                c.prepare("SELECT alias, until FROM chat_mutes WHERE chat_id=?1 AND until>?2")?;
+            let mutes = stmt
+                .query_map(params![chat_id, Utc::now().naive_utc()], |r| {
+                    let until: chrono::NaiveDateTime = r.get(1)?;
+                    Ok((r.get::<_, String>(0)?, until.and_utc()))
+                })?
+                .collect::<Result<_>>()?;
+            Ok(ChatSettings { severities, mutes })
+        })
+        .await
+    }
+
+    /// 设置会话接收的告警级别，`None` 表示全部
+    pub async fn set_severities(&self, chat_id: i64, severities: Option<&[Severity]>) -> Result<()> {
+        let value = severities.map(|list| {
+            list.iter()
+                .map(|s| s.as_str())
+                .collect::<Vec<_>>()
+                .join(",")
+        });
+        self.write(move |c| {
+            c.execute(
+                "INSERT INTO chat_settings(chat_id, severities) VALUES(?1,?2)
+                 ON CONFLICT(chat_id) DO UPDATE SET severities=excluded.severities",
+                params![chat_id, value],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// 静音某个目标（或 `ALL_TARGETS`）直到 `until`
+    pub async fn mute(&self, chat_id: i64, alias: &str, until: DateTime<Utc>) -> Result<()> {
+        let alias = alias.to_owned();
+        self.write(move |c| {
+            c.execute(
+                "INSERT INTO chat_mutes(chat_id, alias, until) VALUES(?1,?2,?3)
+                 ON CONFLICT(chat_id, alias) DO UPDATE SET until=excluded.until",
+                params![chat_id, alias, until.naive_utc()],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// 取消静音，`alias` 为 `None` 时清除该会话的全部静音；返回删除的条数
+    pub async fn unmute(&self, chat_id: i64, alias: Option<&str>) -> Result<usize> {
+        let alias = alias.map(str::to_owned);
+        self.write(move |c| match alias {
+            Some(alias) => c.execute(
+                "DELETE FROM chat_mutes WHERE chat_id=?1 AND alias=?2",
+                params![chat_id, alias],
+            ),
+            None => c.execute("DELETE FROM chat_mutes WHERE chat_id=?1", params![chat_id]),
+        })
+        .await
+    }
+
+    /// 记录一条发往某会话的告警，`delivered` 为 false 表示因静音未推送
+    pub async fn log_alert(
         &self,
+        chat_id: i64,
         alias: &str,
+        severity: Severity,
+        message: &str,
         ts: DateTime<Utc>,
-        latency: f64,
-        loss_rate: f64,
+        delivered: bool,
     ) -> Result<()> {
-        let mut c = self.conn.lock().await;
-        c.execute(
-            "INSERT INTO metrics(alias, ts, latency, loss_rate) VALUES(?1,?2,?3,?4)",
-            params![alias, ts.naive_utc(), latency, loss_rate],
-        )?;
-        Ok(())
+        let (alias, message) = (alias.to_owned(), message.to_owned());
+        self.write(move |c| {
+            c.execute(
+                "INSERT INTO alert_log(chat_id, alias, severity, message, ts, delivered)
+                 VALUES(?1,?2,?3,?4,?5,?6)",
+                params![chat_id, alias, severity.as_str(), message, ts.naive_utc(), delivered],
+            )?;
+            Ok(())
+        })
+        .await
     }
 
-    /// 查询过去 N 小时的延迟数据
-    pub async fn query_metrics(
-        &self,
-        since: DateTime<Utc>,
-    ) -> rusqlite::Result<Vec<(String, DateTime<Utc>, f64, f64)>> {
-        let since_naive = since.naive_utc();
-        let conn = self.conn.clone();
-
-        // 1. spawn_blocking 并显式标注闭包返回 rusqlite::Result<…>
-        let handle = tokio::task::spawn_blocking(
-            move || -> rusqlite::Result<Vec<(String, DateTime<Utc>, f64, f64)>> {
-                let c = conn.blocking_lock();
-                let mut stmt = c.prepare(
-                    "SELECT alias, ts, latency, loss_rate 
-                     FROM metrics 
-                     WHERE ts>=?1 
+    /// 取出会话因静音错过且尚未查看的告警，并标记为已查看
+    pub async fn take_missed_alerts(&self, chat_id: i64) -> Result<Vec<(DateTime<Utc>, String)>> {
+        self.write(move |c| {
+            let tx = c.transaction()?;
+            let missed = {
+                let mut stmt = tx.prepare(
+                    "SELECT ts, message FROM alert_log
+                     WHERE chat_id=?1 AND delivered=0 AND seen=0
                      ORDER BY ts",
                 )?;
-                let rows = stmt.query_map(params![since_naive], |r| {
-                    let alias: String = r.get(0)?;
-                    let naive: chrono::NaiveDateTime = r.get(1)?;
-                    let ts = DateTime::from_naive_utc_and_offset(naive, Utc);
-                    let lat: f64 = r.get(2)?;
-                    let loss: f64 = r.get(3)?;
-                    Ok((alias, ts, lat, loss))
+                let rows = stmt.query_map(params![chat_id], |r| {
+                    let ts: chrono::NaiveDateTime = r.get(0)?;
+                    Ok((ts.and_utc(), r.get::<_, String>(1)?))
                 })?;
+                rows.collect::<Result<Vec<_>>>()?
+            };
+            tx.execute(
+                "UPDATE alert_log SET seen=1 WHERE chat_id=?1 AND delivered=0 AND seen=0",
+                params![chat_id],
+            )?;
+            tx.commit()?;
+            Ok(missed)
+        })
+        .await
+    }
 
-                // collect() 会产出 rusqlite::Result<Vec<…>>
-                rows.collect()
+    /// 会话因静音错过且尚未查看的告警条数
+    pub async fn count_missed_alerts(&self, chat_id: i64) -> Result<usize> {
+        self.read(move |c| {
+            c.query_row(
+                "SELECT COUNT(*) FROM alert_log WHERE chat_id=?1 AND delivered=0 AND seen=0",
+                params![chat_id],
+                |r| r.get(0),
+            )
+        })
+        .await
+    }
+
+    /// 在一个事务中写入一轮探测的结果、证书信息与故障记录的改动
+    ///
+    /// 本轮新建了故障时返回其 id。
+    pub async fn record_round(&self, round: RoundRecord) -> Result<Option<i64>> {
+        self.write(move |c| {
+            let tx = c.transaction()?;
+            let RoundRecord {
+                alias,
+                ts,
+                stats,
+                timings,
+                maintenance,
+                not_after,
+                incident,
+            } = round;
+            tx.execute(
+                "INSERT INTO metrics(alias, ts, latency, loss_rate, dns_ms, connect_ms, tls_ms, ttfb_ms,
+                                     min_ms, max_ms, p50_ms, p95_ms, stddev_ms, jitter_ms, maintenance)
+                 VALUES(?1,?2,?3,?4,?5,?6,?7,?8,?9,?10,?11,?12,?13,?14,?15)",
+                params![
+                    alias,
+                    ts.naive_utc(),
+                    stats.avg,
+                    stats.loss,
+                    timings.dns,
+                    timings.connect,
+                    timings.tls,
+                    timings.ttfb,
+                    stats.min,
+                    stats.max,
+                    stats.p50,
+                    stats.p95,
+                    stats.stddev,
+                    stats.jitter,
+                    maintenance
+                ],
+            )?;
+            if let Some(not_after) = not_after {
+                tx.execute(
+                    "INSERT INTO certs(alias, not_after, checked_at) VALUES(?1,?2,?3)
+                     ON CONFLICT(alias) DO UPDATE SET not_after=excluded.not_after, checked_at=excluded.checked_at",
+                    params![alias, not_after.naive_utc(), ts.naive_utc()],
+                )?;
+            }
+            let mut opened = None;
+            match incident {
+                Some(IncidentUpdate::Open {
+                    start,
+                    cause,
+                    reason,
+                    loss,
+                }) => {
+                    tx.execute(
+                        "INSERT INTO incidents(alias, start_ts, cause, reason, peak_loss) VALUES(?1,?2,?3,?4,?5)",
+                        params![alias, start.naive_utc(), cause, reason, loss],
+                    )?;
+                    opened = Some(tx.last_insert_rowid());
+                }
+                Some(IncidentUpdate::Peak { id, loss }) => {
+                    tx.execute(
+                        "UPDATE incidents SET peak_loss=MAX(peak_loss, ?2) WHERE id=?1",
+                        params![id, loss],
+                    )?;
+                }
+                Some(IncidentUpdate::Close { id, end }) => {
+                    tx.execute(
+                        "UPDATE incidents SET end_ts=?2 WHERE id=?1",
+                        params![id, end.naive_utc()],
+                    )?;
+                }
+                None => {}
+            }
+            tx.commit()?;
+            Ok(opened)
+        })
+        .await
+    }
+
+    /// 添加一次性维护窗口 [start, end)
+    pub async fn add_maintenance(
+        &self,
+        alias: &str,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<()> {
+        let alias = alias.to_owned();
+        self.write(move |c| {
+            c.execute(
+                "INSERT INTO maintenance(alias, start_ts, end_ts) VALUES(?1,?2,?3)",
+                params![alias, start.naive_utc(), end.naive_utc()],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// 目标在 `at` 时是否处于一次性维护窗口
+    pub async fn in_maintenance(&self, alias: &str, at: DateTime<Utc>) -> Result<bool> {
+        let alias = alias.to_owned();
+        self.read(move |c| {
+            let exists: i32 = c.query_row(
+                "SELECT EXISTS(SELECT 1 FROM maintenance WHERE alias=?1 AND start_ts<=?2 AND end_ts>?2)",
+                params![alias, at.naive_utc()],
+                |r| r.get(0),
+            )?;
+            Ok(exists != 0)
+        })
+        .await
+    }
+
+    /// 列出尚未结束的一次性维护窗口：(alias, start, end)
+    pub async fn list_maintenance(
+        &self,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<(String, DateTime<Utc>, DateTime<Utc>)>> {
+        self.read(move |c| {
+            let mut stmt = c.prepare(
+                "SELECT alias, start_ts, end_ts FROM maintenance WHERE end_ts>?1 ORDER BY start_ts",
+            )?;
+            let rows = stmt.query_map(params![now.naive_utc()], |r| {
+                let start: chrono::NaiveDateTime = r.get(1)?;
+                let end: chrono::NaiveDateTime = r.get(2)?;
+                Ok((r.get::<_, String>(0)?, start.and_utc(), end.and_utc()))
+            })?;
+            rows.collect()
+        })
+        .await
+    }
+
+    /// 目标尚未恢复的故障：(id, start)，用于重启后接续
+    pub async fn ongoing_incident(&self, alias: &str) -> Result<Option<(i64, DateTime<Utc>)>> {
+        let alias = alias.to_owned();
+        self.read(move |c| {
+            let row = c.query_row(
+                "SELECT id, start_ts FROM incidents WHERE alias=?1 AND end_ts IS NULL
+                 ORDER BY start_ts DESC LIMIT 1",
+                params![alias],
+                |r| {
+                    let start: chrono::NaiveDateTime = r.get(1)?;
+                    Ok((r.get(0)?, start.and_utc()))
+                },
+            );
+            match row {
+                Ok(v) => Ok(Some(v)),
+                Err(Error::QueryReturnedNoRows) => Ok(None),
+                Err(e) => Err(e),
+            }
+        })
+        .await
+    }
+
+    /// 列出 `since` 之后开始或仍在进行的故障，按开始时间倒序
+    pub async fn list_incidents(
+        &self,
+        since: DateTime<Utc>,
+        alias: Option<&str>,
+    ) -> Result<Vec<Incident>> {
+        let alias = alias.map(str::to_owned);
+        self.read(move |c| {
+            let mut stmt = c.prepare(
+                "SELECT alias, start_ts, end_ts, cause, reason, peak_loss FROM incidents
+                 WHERE (start_ts>=?1 OR end_ts IS NULL OR end_ts>=?1)
+                   AND (?2 IS NULL OR alias=?2)
+                 ORDER BY start_ts DESC",
+            )?;
+            let rows = stmt.query_map(params![since.naive_utc(), alias], |r| {
+                let start: chrono::NaiveDateTime = r.get(1)?;
+                let end: Option<chrono::NaiveDateTime> = r.get(2)?;
+                Ok(Incident {
+                    alias: r.get(0)?,
+                    start: start.and_utc(),
+                    end: end.map(|e| e.and_utc()),
+                    cause: r.get(3)?,
+                    reason: r.get(4)?,
+                    peak_loss: r.get(5)?,
+                })
+            })?;
+            rows.collect()
+        })
+        .await
+    }
+
+    /// 读取一项持久化的运行状态
+    pub async fn get_state(&self, key: &str) -> Result<Option<String>> {
+        let key = key.to_owned();
+        self.read(move |c| {
+            match c.query_row("SELECT value FROM bot_state WHERE key=?1", params![key], |r| r.get(0)) {
+                Ok(v) => Ok(Some(v)),
+                Err(Error::QueryReturnedNoRows) => Ok(None),
+                Err(e) => Err(e),
+            }
+        })
+        .await
+    }
+
+    /// 写入一项持久化的运行状态
+    pub async fn set_state(&self, key: &str, value: &str) -> Result<()> {
+        let (key, value) = (key.to_owned(), value.to_owned());
+        self.write(move |c| {
+            c.execute(
+                "INSERT INTO bot_state(key, value) VALUES(?1,?2)
+                 ON CONFLICT(key) DO UPDATE SET value=excluded.value",
+                params![key, value],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// 查询某个 TLS 目标的证书到期时间及检查时间
+    pub async fn get_cert(&self, alias: &str) -> Result<Option<(DateTime<Utc>, DateTime<Utc>)>> {
+        let alias = alias.to_owned();
+        self.read(move |c| {
+            let row = c.query_row(
+                "SELECT not_after, checked_at FROM certs WHERE alias=?1",
+                params![alias],
+                |r| {
+                    let not_after: chrono::NaiveDateTime = r.get(0)?;
+                    let checked_at: chrono::NaiveDateTime = r.get(1)?;
+                    Ok((not_after.and_utc(), checked_at.and_utc()))
+                },
+            );
+            match row {
+                Ok(v) => Ok(Some(v)),
+                Err(Error::QueryReturnedNoRows) => Ok(None),
+                Err(e) => Err(e),
+            }
+        })
+        .await
+    }
+
+    /// 某精度下最早一条数据的时间，没有数据时为 `None`
+    pub async fn oldest(&self, resolution: Resolution) -> Result<Option<DateTime<Utc>>> {
+        self.read(move |c| {
+            let ts: Option<chrono::NaiveDateTime> = c.query_row(
+                &format!("SELECT MIN(ts) FROM {}", resolution.table()),
+                [],
+                |r| r.get(0),
+            )?;
+            Ok(ts.map(|t| t.and_utc()))
+        })
+        .await
+    }
+
+    /// 按查询跨度挑选精度：优先选择覆盖 `since` 且点数不多的最细精度
+    ///
+    /// 都不覆盖时（例如刚开始运行），选跨度允许的最细、且有数据的精度。
+    pub async fn resolution(&self, since: DateTime<Utc>, until: DateTime<Utc>) -> Result<Resolution> {
+        let range = until - since;
+        let mut fallback = None;
+        for resolution in Resolution::ALL {
+            if range > resolution.max_range() {
+                continue;
+            }
+            match self.oldest(resolution).await? {
+                Some(oldest) if oldest <= since => return Ok(resolution),
+                Some(_) => {
+                    fallback.get_or_insert(resolution);
+                }
+                None => {}
+            }
+        }
+        Ok(fallback.unwrap_or(Resolution::Raw))
+    }
+
+    /// 覆盖 `since` 的最细精度，用于需要尽量精确的统计（如可用率）
+    pub async fn finest_resolution(&self, since: DateTime<Utc>) -> Result<Resolution> {
+        let mut earliest: Option<(Resolution, DateTime<Utc>)> = None;
+        for resolution in Resolution::ALL {
+            let Some(oldest) = self.oldest(resolution).await? else {
+                continue;
+            };
+            if oldest <= since {
+                return Ok(resolution);
+            }
+            if earliest.is_none_or(|(_, t)| oldest < t) {
+                earliest = Some((resolution, oldest));
+            }
+        }
+        Ok(earliest.map_or(Resolution::Raw, |(r, _)| r))
+    }
+
+    /// 查询 [since, until) 区间内的汇总桶，按 alias、时间排序
+    pub async fn query_rollups(
+        &self,
+        resolution: Resolution,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<Vec<Rollup>> {
+        let (since, until) = (since.naive_utc(), until.naive_utc());
+        self.read(move |c| {
+            let mut stmt = c.prepare(&format!(
+                "SELECT alias, ts, rounds, down_rounds, maintenance_rounds
+                 FROM {} WHERE ts>=?1 AND ts<?2 ORDER BY alias, ts",
+                resolution.table()
+            ))?;
+            let rows = stmt.query_map(params![since, until], |r| {
+                let ts: chrono::NaiveDateTime = r.get(1)?;
+                Ok(Rollup {
+                    alias: r.get(0)?,
+                    ts: ts.and_utc(),
+                    rounds: r.get(2)?,
+                    down_rounds: r.get(3)?,
+                    maintenance_rounds: r.get(4)?,
+                })
+            })?;
+            rows.collect()
+        })
+        .await
+    }
+
+    /// 把 [from, to) 内的原始数据按桶汇总进 `resolution` 对应的表，返回写入的桶数
+    ///
+    /// `thresholds` 为各目标的丢包阈值，未列出的目标按 100% 计算不可用轮数。
+    /// 丢包率只取非维护轮次的平均（整桶都在维护时取全部轮次），与 `MetricPoint::loss` 一致。
+    /// 同一个桶重复汇总时覆盖旧值。
+    pub async fn rollup(
+        &self,
+        resolution: Resolution,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        thresholds: HashMap<String, f64>,
+    ) -> Result<usize> {
+        let Some(step) = resolution.step() else {
+            return Ok(0);
+        };
+        let (from, to) = (from.naive_utc(), to.naive_utc());
+        self.write(move |c| {
+            let tx = c.transaction()?;
+            let aliases: Vec<String> = {
+                let mut stmt =
+                    tx.prepare("SELECT DISTINCT alias FROM metrics WHERE ts>=?1 AND ts<?2")?;
+                let rows = stmt.query_map(params![from, to], |r| r.get(0))?;
+                rows.collect::<Result<_>>()?
+            };
+            let sql = format!(
+                "INSERT OR REPLACE INTO {table}(alias, ts, rounds, down_rounds, maintenance_rounds,
+                     latency_min, latency_avg, latency_max, p95_ms, loss_rate)
+                 SELECT alias,
+                        datetime(CAST(strftime('%s', ts) AS INTEGER) / {step} * {step}, 'unixepoch') AS bucket,
+                        COUNT(*),
+                        SUM(maintenance = 0 AND loss_rate >= ?4),
+                        SUM(maintenance),
+                        MIN(COALESCE(min_ms, latency)),
+                        AVG(latency),
+                        MAX(COALESCE(max_ms, latency)),
+                        AVG(p95_ms),
+                        COALESCE(AVG(CASE WHEN maintenance = 0 THEN loss_rate END), AVG(loss_rate))
+                 FROM metrics
+                 WHERE alias=?1 AND ts>=?2 AND ts<?3
+                 GROUP BY bucket",
+                table = resolution.table(),
+                step = step.num_seconds()
+            );
+            let mut buckets = 0;
+            for alias in &aliases {
+                let threshold = thresholds.get(alias).copied().unwrap_or(100.0);
+                buckets += tx.execute(&sql, params![alias, from, to, threshold])?;
+            }
+            tx.commit()?;
+            Ok(buckets)
+        })
+        .await
+    }
+
+    /// 删除某精度下 `before` 之前的数据，返回删除的条数
+    pub async fn prune(&self, resolution: Resolution, before: DateTime<Utc>) -> Result<usize> {
+        self.write(move |c| {
+            c.execute(
+                &format!("DELETE FROM {} WHERE ts<?1", resolution.table()),
+                params![before.naive_utc()],
+            )
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    /// 测试用的临时数据库文件（读连接需要按路径打开，不能用内存库）
+    struct TempDb {
+        path: std::path::PathBuf,
+        db: Db,
+    }
+
+    impl TempDb {
+        async fn new(name: &str) -> Self {
+            let path = std::env::temp_dir()
+                .join(format!("tg_prober_{}_{}.db", name, std::process::id()));
+            let db = Db::new(path.to_str().unwrap()).await.unwrap();
+            TempDb { path, db }
+        }
+    }
+
+    impl Drop for TempDb {
+        fn drop(&mut self) {
+            for suffix in ["", "-wal", "-shm"] {
+                let mut path = self.path.clone().into_os_string();
+                path.push(suffix);
+                let _ = std::fs::remove_file(path);
+            }
+        }
+    }
+
+    fn round(ts: DateTime<Utc>, loss: f64, maintenance: bool) -> RoundRecord {
+        RoundRecord {
+            alias: String::from("hk"),
+            ts,
+            stats: RoundStats {
+                avg: (loss < 100.0).then_some(10.0),
+                loss,
+                ..Default::default()
             },
-        );
+            timings: PhaseTimings::default(),
+            maintenance,
+            not_after: None,
+            incident: None,
+        }
+    }
 
-        // 2. 先处理 JoinError，然后再处理 closure 内部的 rusqlite::Error
-        let rows = handle
+    #[tokio::test]
+    async fn rollup_loss_excludes_maintenance_rounds() {
+        let temp = TempDb::new("rollup_loss").await;
+        let db = &temp.db;
+        let t0 = Utc.with_ymd_and_hms(2026, 10, 1, 0, 0, 0).unwrap();
+        let minute = Duration::minutes(1);
+        // 第一个 5 分钟桶：两轮正常（0%、20%），两轮维护（100%）
+        for (i, (loss, maintenance)) in [(0.0, false), (100.0, true), (20.0, false), (100.0, true)]
+            .into_iter()
+            .enumerate()
+        {
+            db.record_round(round(t0 + minute * i as i32, loss, maintenance))
+                .await
+                .unwrap();
+        }
+        // 第二个桶全部在维护中
+        db.record_round(round(t0 + minute * 5, 100.0, true))
             .await
-            .map_err(|e| {
-                Error::SqliteFailure(
-                    ffi::Error::new(ErrorCode::Unknown as i32),
-                    Some(format!("JoinError: {}", e)),
-                )
-            })?
-            ?;
+            .unwrap();
 
-        Ok(rows)
+        let end = t0 + Duration::minutes(10);
+        db.rollup(Resolution::FiveMinutes, t0, end, HashMap::new())
+            .await
+            .unwrap();
+
+        let points = db
+            .query(MetricQuery::new(t0, end).resolution(Resolution::FiveMinutes))
+            .await
+            .unwrap();
+        let losses: Vec<(u32, u32, f64)> = points
+            .iter()
+            .map(|p| (p.rounds, p.maintenance_rounds, p.loss))
+            .collect();
+        assert_eq!(losses, [(4, 2, 10.0), (1, 1, 100.0)]);
+
+        // 在汇总表上再分桶时，与直接读原始数据的结果一致
+        for resolution in [Resolution::Raw, Resolution::FiveMinutes] {
+            let points = db
+                .query(
+                    MetricQuery::new(t0, end)
+                        .resolution(resolution)
+                        .bucket(Duration::minutes(10)),
+                )
+                .await
+                .unwrap();
+            assert_eq!(points.len(), 1);
+            assert_eq!(points[0].loss, 10.0);
+        }
     }
 }
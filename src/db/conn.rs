@@ -0,0 +1,121 @@
+// src/db/conn.rs
+//! 连接管理：写操作交给独占写连接的后台线程串行执行，读操作在只读连接池中并发执行
+//!
+//! 数据库使用 WAL 模式，读连接不会被写事务阻塞。
+use rusqlite::{ffi, Connection, Error, ErrorCode, OpenFlags, Result};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+/// 等待其他连接释放锁的最长时间
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+/// 保留的空闲只读连接数
+const MAX_IDLE_READERS: usize = 4;
+
+type Job = Box<dyn FnOnce(&mut Connection) + Send>;
+
+/// 非 rusqlite 本身产生的错误（线程退出、任务 panic 等）
+fn failure(message: String) -> Error {
+    Error::SqliteFailure(ffi::Error::new(ErrorCode::Unknown as i32), Some(message))
+}
+
+/// 为写连接开启 WAL 模式
+pub(super) fn configure_writer(conn: &Connection) -> Result<()> {
+    conn.busy_timeout(BUSY_TIMEOUT)?;
+    let mode: String =
+        conn.pragma_update_and_check(None, "journal_mode", "WAL", |r| r.get(0))?;
+    if !mode.eq_ignore_ascii_case("wal") {
+        log::warn!("数据库未能切换到 WAL 模式（当前 {}），读写可能互相阻塞", mode);
+    }
+    conn.pragma_update(None, "synchronous", "NORMAL")
+}
+
+/// 独占写连接的后台线程
+pub(super) struct Writer {
+    jobs: mpsc::Sender<Job>,
+}
+
+impl Writer {
+    pub fn spawn(mut conn: Connection) -> Result<Self> {
+        let (jobs, rx) = mpsc::channel::<Job>();
+        thread::Builder::new()
+            .name(String::from("db-writer"))
+            .spawn(move || {
+                for job in rx {
+                    // 单个任务 panic 时丢弃其结果，写线程继续服务
+                    if panic::catch_unwind(AssertUnwindSafe(|| job(&mut conn))).is_err() {
+                        log::error!("数据库写任务 panic");
+                    }
+                }
+            })
+            .map_err(|e| failure(format!("启动数据库写线程失败: {}", e)))?;
+        Ok(Writer { jobs })
+    }
+
+    /// 在写线程上执行 `f` 并等待结果
+    pub async fn call<T, F>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&mut Connection) -> Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let (reply, rx) = oneshot::channel();
+        self.jobs
+            .send(Box::new(move |c| {
+                let _ = reply.send(f(c));
+            }))
+            .map_err(|_| failure(String::from("数据库写线程已退出")))?;
+        rx.await
+            .map_err(|_| failure(String::from("数据库写任务未返回结果")))?
+    }
+}
+
+/// 只读连接池，连接按需打开，用完放回
+pub(super) struct Readers {
+    path: PathBuf,
+    idle: Mutex<Vec<Connection>>,
+}
+
+impl Readers {
+    pub fn new(path: impl Into<PathBuf>) -> Arc<Self> {
+        Arc::new(Readers {
+            path: path.into(),
+            idle: Mutex::new(Vec::new()),
+        })
+    }
+
+    fn open(&self) -> Result<Connection> {
+        let conn = Connection::open_with_flags(
+            &self.path,
+            OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )?;
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        Ok(conn)
+    }
+
+    /// 在阻塞线程池中用一个只读连接执行 `f`
+    pub async fn call<T, F>(self: &Arc<Self>, f: F) -> Result<T>
+    where
+        F: FnOnce(&Connection) -> Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let readers = self.clone();
+        tokio::task::spawn_blocking(move || {
+            let idle = readers.idle.lock().unwrap_or_else(|e| e.into_inner()).pop();
+            let conn = match idle {
+                Some(conn) => conn,
+                None => readers.open()?,
+            };
+            let result = f(&conn);
+            let mut idle = readers.idle.lock().unwrap_or_else(|e| e.into_inner());
+            if idle.len() < MAX_IDLE_READERS {
+                idle.push(conn);
+            }
+            result
+        })
+        .await
+        .map_err(|e| failure(format!("JoinError: {}", e)))?
+    }
+}
@@ -0,0 +1,383 @@
+// src/db/migrations.rs
+//! 数据库表结构的版本管理
+//!
+//! 每个版本对应一个迁移步骤，启动时按顺序在各自的事务中执行，
+//! 完成后记入 `schema_version`。修改表结构时在 `MIGRATIONS` 末尾追加新步骤，
+//! 不要修改已发布的步骤。
+use chrono::Utc;
+use log::info;
+use rusqlite::{ffi, params, Connection, Error, ErrorCode, Result, Transaction};
+
+/// 一个迁移步骤
+struct Migration {
+    version: u32,
+    description: &'static str,
+    apply: fn(&Transaction) -> Result<()>,
+}
+
+/// 按版本号升序排列
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "初始表结构",
+        apply: baseline,
+    },
+    Migration {
+        version: 2,
+        description: "metrics.latency 允许为空，离线轮次不再记为 0 ms",
+        apply: nullable_latency,
+    },
+    Migration {
+        version: 3,
+        description: "metrics 的 5 分钟与 1 小时汇总表",
+        apply: rollup_tables,
+    },
+    Migration {
+        version: 4,
+        description: "metrics 按目标与时间查询的索引",
+        apply: alias_index,
+    },
+];
+
+/// 把数据库迁移到最新版本；数据库版本比程序新时拒绝启动
+pub(super) fn migrate(conn: &mut Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS schema_version (
+            version     INTEGER PRIMARY KEY,
+            description TEXT     NOT NULL,
+            applied_at  DATETIME NOT NULL
+        )",
+    )?;
+    let current: u32 = conn.query_row(
+        "SELECT COALESCE(MAX(version), 0) FROM schema_version",
+        [],
+        |r| r.get(0),
+    )?;
+    let latest = MIGRATIONS.last().map_or(0, |m| m.version);
+    if current > latest {
+        return Err(Error::SqliteFailure(
+            ffi::Error::new(ErrorCode::CannotOpen as i32),
+            Some(format!(
+                "数据库版本 {} 高于程序支持的版本 {}，请升级程序",
+                current, latest
+            )),
+        ));
+    }
+    for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+        // 中途失败时事务随 tx 丢弃而回滚，版本号保持不变
+        let tx = conn.transaction()?;
+        (migration.apply)(&tx)?;
+        tx.execute(
+            "INSERT INTO schema_version(version, description, applied_at) VALUES(?1,?2,?3)",
+            params![migration.version, migration.description, Utc::now().naive_utc()],
+        )?;
+        tx.commit()?;
+        info!("数据库已迁移到版本 {}：{}", migration.version, migration.description);
+    }
+    Ok(())
+}
+
+/// v1：引入版本管理之前的表结构
+///
+/// 旧库没有 `schema_version`，会从这里开始执行，因此建表都带 `IF NOT EXISTS`，
+/// 并为更早的库补齐后来追加的列。
+fn baseline(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        r#"
+        CREATE TABLE IF NOT EXISTS subscriptions (
+            chat_id   INTEGER PRIMARY KEY
+        );
+        CREATE TABLE IF NOT EXISTS target_chats (
+            alias     TEXT    NOT NULL,
+            chat_id   INTEGER NOT NULL,
+            PRIMARY KEY (alias, chat_id)
+        );
+        CREATE TABLE IF NOT EXISTS chat_settings (
+            chat_id    INTEGER PRIMARY KEY,
+            severities TEXT
+        );
+        CREATE TABLE IF NOT EXISTS chat_mutes (
+            chat_id   INTEGER  NOT NULL,
+            alias     TEXT     NOT NULL,
+            until     DATETIME NOT NULL,
+            PRIMARY KEY (chat_id, alias)
+        );
+        CREATE TABLE IF NOT EXISTS alert_log (
+            id        INTEGER PRIMARY KEY AUTOINCREMENT,
+            chat_id   INTEGER  NOT NULL,
+            alias     TEXT     NOT NULL,
+            severity  TEXT     NOT NULL,
+            message   TEXT     NOT NULL,
+            ts        DATETIME NOT NULL,
+            delivered INTEGER  NOT NULL,
+            seen      INTEGER  NOT NULL DEFAULT 0
+        );
+        CREATE INDEX IF NOT EXISTS idx_alert_log_chat
+            ON alert_log(chat_id, delivered, seen);
+        CREATE TABLE IF NOT EXISTS metrics (
+            id        INTEGER PRIMARY KEY AUTOINCREMENT,
+            alias     TEXT    NOT NULL,
+            ts        DATETIME NOT NULL,
+            latency   REAL    NOT NULL,
+            loss_rate REAL    NOT NULL,
+            dns_ms     REAL,
+            connect_ms REAL,
+            tls_ms     REAL,
+            ttfb_ms    REAL,
+            min_ms     REAL,
+            max_ms     REAL,
+            p50_ms     REAL,
+            p95_ms     REAL,
+            stddev_ms  REAL,
+            jitter_ms  REAL,
+            maintenance INTEGER NOT NULL DEFAULT 0
+        );
+        CREATE INDEX IF NOT EXISTS idx_metrics_ts_alias
+            ON metrics(ts, alias);
+        CREATE TABLE IF NOT EXISTS targets (
+            alias      TEXT PRIMARY KEY,
+            address    TEXT NOT NULL,
+            kind       TEXT NOT NULL,
+            created_at DATETIME NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS maintenance (
+            id         INTEGER PRIMARY KEY AUTOINCREMENT,
+            alias      TEXT     NOT NULL,
+            start_ts   DATETIME NOT NULL,
+            end_ts     DATETIME NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_maintenance_alias
+            ON maintenance(alias, end_ts);
+        CREATE TABLE IF NOT EXISTS incidents (
+            id         INTEGER PRIMARY KEY AUTOINCREMENT,
+            alias      TEXT     NOT NULL,
+            start_ts   DATETIME NOT NULL,
+            end_ts     DATETIME,
+            cause      TEXT     NOT NULL,
+            reason     TEXT     NOT NULL,
+            peak_loss  REAL     NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_incidents_start
+            ON incidents(start_ts, alias);
+        CREATE TABLE IF NOT EXISTS bot_state (
+            key        TEXT PRIMARY KEY,
+            value      TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS certs (
+            alias      TEXT PRIMARY KEY,
+            not_after  DATETIME NOT NULL,
+            checked_at DATETIME NOT NULL
+        );
+        "#,
+    )?;
+    // 旧库补齐 HTTP 分阶段耗时列与延迟分布列
+    for col in [
+        "dns_ms", "connect_ms", "tls_ms", "ttfb_ms", "min_ms", "max_ms", "p50_ms", "p95_ms",
+        "stddev_ms", "jitter_ms",
+    ] {
+        ensure_column(tx, "metrics", col, "REAL")?;
+    }
+    ensure_column(tx, "metrics", "maintenance", "INTEGER NOT NULL DEFAULT 0")
+}
+
+/// v2：SQLite 不能修改列约束，只能重建 metrics；旧数据中全部丢包的轮次改为 NULL
+fn nullable_latency(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        r#"
+        CREATE TABLE metrics_new (
+            id        INTEGER PRIMARY KEY AUTOINCREMENT,
+            alias     TEXT    NOT NULL,
+            ts        DATETIME NOT NULL,
+            latency   REAL,
+            loss_rate REAL    NOT NULL,
+            dns_ms     REAL,
+            connect_ms REAL,
+            tls_ms     REAL,
+            ttfb_ms    REAL,
+            min_ms     REAL,
+            max_ms     REAL,
+            p50_ms     REAL,
+            p95_ms     REAL,
+            stddev_ms  REAL,
+            jitter_ms  REAL,
+            maintenance INTEGER NOT NULL DEFAULT 0
+        );
+        INSERT INTO metrics_new(id, alias, ts, latency, loss_rate, dns_ms, connect_ms, tls_ms,
+                                ttfb_ms, min_ms, max_ms, p50_ms, p95_ms, stddev_ms, jitter_ms,
+                                maintenance)
+            SELECT id, alias, ts,
+                   CASE WHEN loss_rate >= 100 THEN NULL ELSE latency END,
+                   loss_rate, dns_ms, connect_ms, tls_ms, ttfb_ms, min_ms, max_ms, p50_ms,
+                   p95_ms, stddev_ms, jitter_ms, maintenance
+            FROM metrics;
+        DROP TABLE metrics;
+        ALTER TABLE metrics_new RENAME TO metrics;
+        CREATE INDEX idx_metrics_ts_alias ON metrics(ts, alias);
+        "#,
+    )
+}
+
+/// v3：降采样用的汇总表，见 `retention`
+fn rollup_tables(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        r#"
+        CREATE TABLE IF NOT EXISTS metrics_5m (
+            alias       TEXT     NOT NULL,
+            ts          DATETIME NOT NULL,
+            rounds      INTEGER  NOT NULL,
+            down_rounds INTEGER  NOT NULL,
+            maintenance_rounds INTEGER NOT NULL,
+            latency_min REAL,
+            latency_avg REAL,
+            latency_max REAL,
+            p95_ms      REAL,
+            loss_rate   REAL     NOT NULL,
+            PRIMARY KEY (alias, ts)
+        );
+        CREATE INDEX IF NOT EXISTS idx_metrics_5m_ts
+            ON metrics_5m(ts);
+        CREATE TABLE IF NOT EXISTS metrics_1h (
+            alias       TEXT     NOT NULL,
+            ts          DATETIME NOT NULL,
+            rounds      INTEGER  NOT NULL,
+            down_rounds INTEGER  NOT NULL,
+            maintenance_rounds INTEGER NOT NULL,
+            latency_min REAL,
+            latency_avg REAL,
+            latency_max REAL,
+            p95_ms      REAL,
+            loss_rate   REAL     NOT NULL,
+            PRIMARY KEY (alias, ts)
+        );
+        CREATE INDEX IF NOT EXISTS idx_metrics_1h_ts
+            ON metrics_1h(ts);
+        "#,
+    )
+}
+
+/// v4：按目标集合与时间窗口查询时使用
+fn alias_index(tx: &Transaction) -> Result<()> {
+    tx.execute_batch("CREATE INDEX IF NOT EXISTS idx_metrics_alias_ts ON metrics(alias, ts)")
+}
+
+/// 若表中缺少某列则追加（用于旧库升级）
+fn ensure_column(conn: &Connection, table: &str, column: &str, decl: &str) -> Result<()> {
+    let exists: bool = conn.query_row(
+        &format!("SELECT EXISTS(SELECT 1 FROM pragma_table_info('{}') WHERE name=?1)", table),
+        params![column],
+        |r| r.get(0),
+    )?;
+    if !exists {
+        conn.execute_batch(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, decl))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 引入版本管理之前的旧库：metrics 只有最早的几列，latency 不允许为空
+    fn legacy_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            r#"
+            CREATE TABLE subscriptions (chat_id INTEGER PRIMARY KEY);
+            CREATE TABLE metrics (
+                id        INTEGER PRIMARY KEY AUTOINCREMENT,
+                alias     TEXT    NOT NULL,
+                ts        DATETIME NOT NULL,
+                latency   REAL    NOT NULL,
+                loss_rate REAL    NOT NULL
+            );
+            INSERT INTO subscriptions(chat_id) VALUES (42);
+            INSERT INTO metrics(alias, ts, latency, loss_rate) VALUES
+                ('hk', '2026-10-01 00:00:00', 12.5, 0),
+                ('hk', '2026-10-01 00:01:00', 0, 100),
+                ('jp', '2026-10-01 00:00:00', 80, 20);
+            "#,
+        )
+        .unwrap();
+        conn
+    }
+
+    fn version(conn: &Connection) -> u32 {
+        conn.query_row("SELECT MAX(version) FROM schema_version", [], |r| r.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn upgrades_legacy_db_to_latest() {
+        let mut conn = legacy_db();
+        migrate(&mut conn).unwrap();
+        assert_eq!(version(&conn), 4);
+
+        // v2 重建 metrics 后旧数据仍在，全部丢包的轮次延迟改为 NULL
+        let rows: Vec<(String, Option<f64>, f64, bool)> = conn
+            .prepare("SELECT alias, latency, loss_rate, maintenance FROM metrics ORDER BY id")
+            .unwrap()
+            .query_map([], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?)))
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(
+            rows,
+            [
+                (String::from("hk"), Some(12.5), 0.0, false),
+                (String::from("hk"), None, 100.0, false),
+                (String::from("jp"), Some(80.0), 20.0, false),
+            ]
+        );
+        let subscribed: i64 = conn
+            .query_row("SELECT chat_id FROM subscriptions", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(subscribed, 42);
+
+        // 新列、汇总表与索引都已建立，离线轮次可以写入 NULL
+        conn.execute(
+            "INSERT INTO metrics(alias, ts, latency, loss_rate, p95_ms) VALUES('hk', ?1, NULL, 100, NULL)",
+            params![Utc::now().naive_utc()],
+        )
+        .unwrap();
+        for table in ["metrics_5m", "metrics_1h"] {
+            conn.execute_batch(&format!("SELECT COUNT(*) FROM {}", table)).unwrap();
+        }
+        let indexed: bool = conn
+            .query_row(
+                "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE name='idx_metrics_alias_ts')",
+                [],
+                |r| r.get(0),
+            )
+            .unwrap();
+        assert!(indexed);
+
+        // 再次执行不做任何改动
+        migrate(&mut conn).unwrap();
+        let steps: u32 = conn
+            .query_row("SELECT COUNT(*) FROM schema_version", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(steps, 4);
+    }
+
+    #[test]
+    fn creates_fresh_db() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrate(&mut conn).unwrap();
+        assert_eq!(version(&conn), 4);
+    }
+
+    #[test]
+    fn rejects_newer_db() {
+        let mut conn = legacy_db();
+        migrate(&mut conn).unwrap();
+        conn.execute(
+            "INSERT INTO schema_version(version, description, applied_at) VALUES(99, 'future', ?1)",
+            params![Utc::now().naive_utc()],
+        )
+        .unwrap();
+        let err = migrate(&mut conn).unwrap_err();
+        assert!(err.to_string().contains("99"), "{}", err);
+        // 不会改动数据库
+        assert_eq!(version(&conn), 99);
+    }
+}
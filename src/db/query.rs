@@ -0,0 +1,309 @@
+// src/db/query.rs
+//! 按目标与时间窗口查询 metrics，分桶与聚合都在 SQL 中完成
+use super::{Db, Resolution};
+use chrono::{DateTime, Duration, Utc};
+use rusqlite::{Result, ToSql};
+use serde::Serialize;
+
+/// 分桶时延迟的聚合方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Aggregate {
+    /// 平均延迟
+    #[default]
+    Avg,
+    /// 最低延迟（每轮的最小值）
+    Min,
+    /// 最高延迟（每轮的最大值）
+    Max,
+}
+
+/// 一次 metrics 查询：时间窗口 [since, until)，可限定目标、分桶与聚合方式
+#[derive(Debug, Clone)]
+pub struct MetricQuery {
+    since: DateTime<Utc>,
+    until: DateTime<Utc>,
+    aliases: Option<Vec<String>>,
+    bucket: Option<Duration>,
+    aggregate: Aggregate,
+    resolution: Option<Resolution>,
+}
+
+impl MetricQuery {
+    /// 查询所有目标在 [since, until) 内的每一条记录
+    pub fn new(since: DateTime<Utc>, until: DateTime<Utc>) -> Self {
+        MetricQuery {
+            since,
+            until,
+            aliases: None,
+            bucket: None,
+            aggregate: Aggregate::Avg,
+            resolution: None,
+        }
+    }
+
+    /// 只查询这些目标
+    pub fn aliases<I, S>(mut self, aliases: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.aliases = Some(aliases.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// 以 `since` 为起点按 `bucket` 分桶，每个目标每桶一条
+    pub fn bucket(mut self, bucket: Duration) -> Self {
+        self.bucket = Some(bucket).filter(|b| b.num_seconds() > 0);
+        self
+    }
+
+    /// 分桶或读取汇总表时延迟的聚合方式，默认平均值
+    pub fn aggregate(mut self, aggregate: Aggregate) -> Self {
+        self.aggregate = aggregate;
+        self
+    }
+
+    /// 指定数据精度，默认按跨度自动选择（见 `Db::resolution`）
+    pub fn resolution(mut self, resolution: Resolution) -> Self {
+        self.resolution = Some(resolution);
+        self
+    }
+}
+
+/// 查询结果中的一个点：一轮探测、一个汇总桶或一个查询桶
+#[derive(Debug, Clone)]
+pub struct MetricPoint {
+    pub alias: String,
+    /// 记录时间，分桶时为桶的起点
+    pub ts: DateTime<Utc>,
+    /// 包含的轮数
+    pub rounds: u32,
+    /// 其中处于维护窗口的轮数
+    pub maintenance_rounds: u32,
+    /// 按 `Aggregate` 聚合的延迟，全部轮次都失败时为 `None`
+    pub latency: Option<f64>,
+    /// 平均 p95 延迟
+    pub p95: Option<f64>,
+    /// 平均丢包率 (%)，不计维护中的轮次（整桶都在维护时除外）
+    pub loss: f64,
+}
+
+/// metrics 表中的一条原始记录，字段与表结构一一对应，用于导出
+#[derive(Debug, Clone, Serialize)]
+pub struct MetricRecord {
+    pub alias: String,
+    pub ts: DateTime<Utc>,
+    /// 平均延迟 (ms)，全部探测失败时为空
+    pub latency_ms: Option<f64>,
+    /// 丢包率 (%)
+    pub loss_rate: f64,
+    pub min_ms: Option<f64>,
+    pub max_ms: Option<f64>,
+    pub p50_ms: Option<f64>,
+    pub p95_ms: Option<f64>,
+    pub stddev_ms: Option<f64>,
+    pub jitter_ms: Option<f64>,
+    pub dns_ms: Option<f64>,
+    pub connect_ms: Option<f64>,
+    pub tls_ms: Option<f64>,
+    pub ttfb_ms: Option<f64>,
+    /// 是否处于维护窗口
+    pub maintenance: bool,
+}
+
+impl MetricPoint {
+    /// 是否全部处于维护窗口
+    pub fn maintenance(&self) -> bool {
+        self.maintenance_rounds >= self.rounds
+    }
+}
+
+/// 各精度下每条记录的列表达式
+struct Columns {
+    rounds: &'static str,
+    maintenance: &'static str,
+    min: &'static str,
+    avg: &'static str,
+    max: &'static str,
+}
+
+impl Resolution {
+    fn columns(self) -> Columns {
+        match self {
+            Resolution::Raw => Columns {
+                rounds: "1",
+                maintenance: "maintenance",
+                min: "COALESCE(min_ms, latency)",
+                avg: "latency",
+                max: "COALESCE(max_ms, latency)",
+            },
+            _ => Columns {
+                rounds: "rounds",
+                maintenance: "maintenance_rounds",
+                min: "latency_min",
+                avg: "latency_avg",
+                max: "latency_max",
+            },
+        }
+    }
+}
+
+impl MetricQuery {
+    /// 生成 SQL，参数依次为 since、until 与各个 alias
+    fn sql(&self, resolution: Resolution) -> String {
+        let Columns {
+            rounds,
+            maintenance,
+            min,
+            avg,
+            max,
+        } = resolution.columns();
+        let filter = match &self.aliases {
+            Some(aliases) => format!(
+                " AND alias IN ({})",
+                (0..aliases.len())
+                    .map(|i| format!("?{}", i + 3))
+                    .collect::<Vec<_>>()
+                    .join(",")
+            ),
+            None => String::new(),
+        };
+        let table = resolution.table();
+        let Some(bucket) = self.bucket else {
+            let latency = match self.aggregate {
+                Aggregate::Avg => avg,
+                Aggregate::Min => min,
+                Aggregate::Max => max,
+            };
+            return format!(
+                "SELECT alias, ts, {rounds}, {maintenance}, {latency}, p95_ms, loss_rate
+                 FROM {table}
+                 WHERE ts>=?1 AND ts<?2{filter}
+                 ORDER BY alias, ts"
+            );
+        };
+        let latency = match self.aggregate {
+            Aggregate::Avg => format!(
+                "SUM({avg} * {rounds}) / SUM(CASE WHEN {avg} IS NOT NULL THEN {rounds} END)"
+            ),
+            Aggregate::Min => format!("MIN({min})"),
+            Aggregate::Max => format!("MAX({max})"),
+        };
+        // 第二列为从 since 起的桶序号；丢包率按轮数加权，优先只算非维护轮次
+        let (origin, step) = (self.since.timestamp(), bucket.num_seconds());
+        format!(
+            "SELECT alias,
+                    (CAST(strftime('%s', ts) AS INTEGER) - {origin}) / {step} AS bucket,
+                    SUM({rounds}),
+                    SUM({maintenance}),
+                    {latency},
+                    AVG(p95_ms),
+                    COALESCE(
+                        SUM(loss_rate * ({rounds} - {maintenance}))
+                            / NULLIF(SUM({rounds} - {maintenance}), 0),
+                        SUM(loss_rate * {rounds}) / SUM({rounds}))
+             FROM {table}
+             WHERE ts>=?1 AND ts<?2{filter}
+             GROUP BY alias, bucket
+             ORDER BY alias, bucket"
+        )
+    }
+}
+
+impl Db {
+    /// 按条件查询 metrics，结果按 alias、时间排序
+    pub async fn query(&self, query: MetricQuery) -> Result<Vec<MetricPoint>> {
+        if query.aliases.as_ref().is_some_and(Vec::is_empty) {
+            return Ok(Vec::new());
+        }
+        let resolution = match query.resolution {
+            Some(r) => r,
+            None => self.resolution(query.since, query.until).await?,
+        };
+        let sql = query.sql(resolution);
+        let (origin, bucket) = (query.since, query.bucket);
+        self.read(move |c| {
+            let (since, until) = (query.since.naive_utc(), query.until.naive_utc());
+            let mut args: Vec<&dyn ToSql> = vec![&since, &until];
+            for alias in query.aliases.iter().flatten() {
+                args.push(alias);
+            }
+            let mut stmt = c.prepare(&sql)?;
+            let rows = stmt.query_map(&*args, |r| {
+                let ts = match bucket {
+                    Some(bucket) => origin + bucket * r.get::<_, i32>(1)?,
+                    None => r.get::<_, chrono::NaiveDateTime>(1)?.and_utc(),
+                };
+                Ok(MetricPoint {
+                    alias: r.get(0)?,
+                    ts,
+                    rounds: r.get(2)?,
+                    maintenance_rounds: r.get(3)?,
+                    latency: r.get(4)?,
+                    p95: r.get(5)?,
+                    loss: r.get(6)?,
+                })
+            })?;
+            rows.collect()
+        })
+        .await
+    }
+
+    /// 读取 [since, until) 内的原始记录，`aliases` 为 `None` 时包含所有目标；按 alias、时间排序
+    pub async fn metric_records(
+        &self,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+        aliases: Option<Vec<String>>,
+    ) -> Result<Vec<MetricRecord>> {
+        if aliases.as_ref().is_some_and(Vec::is_empty) {
+            return Ok(Vec::new());
+        }
+        let (since, until) = (since.naive_utc(), until.naive_utc());
+        self.read(move |c| {
+            let filter = match &aliases {
+                Some(aliases) => format!(
+                    " AND alias IN ({})",
+                    (0..aliases.len())
+                        .map(|i| format!("?{}", i + 3))
+                        .collect::<Vec<_>>()
+                        .join(",")
+                ),
+                None => String::new(),
+            };
+            let mut stmt = c.prepare(&format!(
+                "SELECT alias, ts, latency, loss_rate, min_ms, max_ms, p50_ms, p95_ms, stddev_ms,
+                        jitter_ms, dns_ms, connect_ms, tls_ms, ttfb_ms, maintenance
+                 FROM metrics
+                 WHERE ts>=?1 AND ts<?2{filter}
+                 ORDER BY alias, ts"
+            ))?;
+            let mut args: Vec<&dyn ToSql> = vec![&since, &until];
+            for alias in aliases.iter().flatten() {
+                args.push(alias);
+            }
+            let rows = stmt.query_map(&*args, |r| {
+                let ts: chrono::NaiveDateTime = r.get(1)?;
+                Ok(MetricRecord {
+                    alias: r.get(0)?,
+                    ts: ts.and_utc(),
+                    latency_ms: r.get(2)?,
+                    loss_rate: r.get(3)?,
+                    min_ms: r.get(4)?,
+                    max_ms: r.get(5)?,
+                    p50_ms: r.get(6)?,
+                    p95_ms: r.get(7)?,
+                    stddev_ms: r.get(8)?,
+                    jitter_ms: r.get(9)?,
+                    dns_ms: r.get(10)?,
+                    connect_ms: r.get(11)?,
+                    tls_ms: r.get(12)?,
+                    ttfb_ms: r.get(13)?,
+                    maintenance: r.get(14)?,
+                })
+            })?;
+            rows.collect()
+        })
+        .await
+    }
+}
@@ -0,0 +1,137 @@
+// src/export.rs
+//! 导出 metrics 原始记录，`/export` 命令与命令行 `export` 子命令共用同一套参数与序列化
+//!
+//! 只导出原始数据；已被清理（只剩汇总）的时间段不包含在内，并提示实际起点。
+use crate::alert::parse_duration;
+use crate::commands::graph::parse_range;
+use crate::db::{Db, MetricRecord, Resolution};
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Duration, Local, Utc};
+use std::io::{self, BufWriter, Write};
+use std::str::FromStr;
+
+/// 命令行子命令的用法
+const CLI_USAGE: &str = "用法：tg_prober export [别名] [时间跨度=24h] [csv|json]";
+
+/// 导出格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Format {
+    #[default]
+    Csv,
+    Json,
+}
+
+impl Format {
+    /// 文件扩展名
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Csv => "csv",
+            Format::Json => "json",
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "csv" => Ok(Format::Csv),
+            "json" => Ok(Format::Json),
+            _ => Err(anyhow!("未知格式: {}（可选 csv、json）", s)),
+        }
+    }
+}
+
+/// 导出参数：`[别名] [时间跨度] [csv|json]`，顺序不限
+#[derive(Debug, Clone)]
+pub struct ExportArgs {
+    /// 为 `None` 时导出全部目标
+    pub alias: Option<String>,
+    pub range: Duration,
+    pub format: Format,
+}
+
+impl ExportArgs {
+    /// 依次尝试时长、格式，其余视为目标别名（最多一个）
+    ///
+    /// 形如时长但超出允许跨度的参数报错，而不是当作别名。
+    pub fn parse(args: &str) -> Result<Self> {
+        let mut parsed = ExportArgs {
+            alias: None,
+            range: Duration::hours(24),
+            format: Format::Csv,
+        };
+        for arg in args.split_whitespace() {
+            if parse_duration(arg).is_ok() {
+                parsed.range = parse_range(arg)?;
+            } else if let Ok(format) = arg.parse() {
+                parsed.format = format;
+            } else if let Some(alias) = parsed.alias.replace(arg.to_string()) {
+                return Err(anyhow!("只能指定一个目标: {} / {}", alias, arg));
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+/// 按格式写出记录：CSV 首行为表头，JSON 为对象数组
+pub fn write_records<W: Write>(records: &[MetricRecord], format: Format, out: W) -> Result<()> {
+    match format {
+        Format::Csv => {
+            let mut writer = csv::Writer::from_writer(out);
+            for record in records {
+                writer.serialize(record)?;
+            }
+            writer.flush()?;
+        }
+        Format::Json => {
+            let mut out = out;
+            serde_json::to_writer_pretty(&mut out, records)?;
+            writeln!(out)?;
+            out.flush()?;
+        }
+    }
+    Ok(())
+}
+
+/// 实际可导出的起点：原始数据只保留一段时间（见 `retention.raw`），
+/// 更早的部分已降采样，起点推迟到最早的一条原始记录；推迟时返回 `(起点, true)`
+pub async fn export_since(
+    db: &Db,
+    range: Duration,
+    now: DateTime<Utc>,
+) -> rusqlite::Result<(DateTime<Utc>, bool)> {
+    let since = now - range;
+    let Some(oldest) = db.oldest(Resolution::Raw).await? else {
+        return Ok((since, false));
+    };
+    if oldest <= since {
+        return Ok((since, false));
+    }
+    // 只有汇总表里还有更早的数据，才说明原始数据被清理过（而不是刚开始监测）
+    for resolution in [Resolution::FiveMinutes, Resolution::Hourly] {
+        if db.oldest(resolution).await?.is_some_and(|t| t < oldest) {
+            return Ok((oldest, true));
+        }
+    }
+    Ok((since, false))
+}
+
+/// 命令行 `export` 子命令：把过去一段时间的原始记录写到标准输出
+pub async fn run_cli(db_path: &str, args: &str) -> Result<()> {
+    let args = ExportArgs::parse(args).map_err(|e| anyhow!("{}\n{}", e, CLI_USAGE))?;
+    let db = Db::new(db_path).await?;
+    let now = Utc::now();
+    let (since, clamped) = export_since(&db, args.range, now).await?;
+    if clamped {
+        eprintln!(
+            "注意：更早的原始数据已被清理（仅保留汇总），只导出 {} 起的记录",
+            since.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S")
+        );
+    }
+    let records = db
+        .metric_records(since, now, args.alias.map(|a| vec![a]))
+        .await?;
+    write_records(&records, args.format, BufWriter::new(io::stdout().lock()))
+}
@@ -1,23 +1,38 @@
 // src/main.rs
 
+mod alert;
 mod cmd;
 mod commands;
 mod config;
 mod db;
+mod export;
+mod maintenance;
 mod monitor;
+mod probe;
+mod retention;
+mod sla;
+mod targets;
 
 use anyhow::Result;
 use chrono::Local;
 use env_logger::Builder;
-use log::{debug, info};
+use log::info;
 use std::io::Write;
-use std::net::SocketAddr;
 use std::sync::Arc;
 use teloxide::Bot;
-use tokio::sync::Mutex;
+use tokio::sync::Semaphore;
+
+/// 数据库文件路径
+const DB_PATH: &str = "db.db";
 
 #[tokio::main(flavor = "multi_thread", worker_threads = 4)]
 async fn main() -> Result<()> {
+    // —— 命令行子命令：导出数据后直接退出 —— //
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("export") {
+        return export::run_cli(DB_PATH, &args[1..].join(" ")).await;
+    }
+
     // —— 加载配置 —— //
     let cfg = config::Config::load("config.toml")?;
 
@@ -37,24 +52,43 @@ async fn main() -> Result<()> {
     info!("日志级别 = {}", cfg.log_level());
 
     // —— 初始化数据库 —— //
-    let db = db::Db::new("db.db").await?;
+    let db = db::Db::new(DB_PATH).await?;
     let db = Arc::new(db); // shareable cloneable Db
     info!("Database Initialization Complete");
     // —— 构造监测目标列表 —— //
-    let targets: Vec<(SocketAddr, String)> = cfg
-        .targets
-        .iter()
-        .map(|t| (t.address.parse().unwrap(), t.alias.clone()))
-        .collect();
-    info!("targets: {:?}", targets);
+    let targets = targets::Targets::new(cfg.targets.clone(), db.list_targets().await?);
+    info!(
+        "targets: {:?}",
+        targets
+            .snapshot()
+            .iter()
+            .map(|t| (t.alias.as_str(), t.kind, t.address.as_str()))
+            .collect::<Vec<_>>()
+    );
+
+    // —— 预加载绘图字体 —— //
+    info!("已加载 {} 个字体", commands::graph::load_fonts());
+
+    let bot = Bot::new(cfg.token.clone());
+    // 后台监测与 /isonline 共用的并发探测上限
+    let limiter = Arc::new(Semaphore::new(cfg.concurrency()));
 
     // —— 启动后台监测任务 —— //
-    monitor::spawn_monitor(cfg.clone(), db.clone(), targets.clone());
-    info!("Spawning {} targets", targets.len());
+    monitor::spawn_monitor(
+        cfg.clone(),
+        db.clone(),
+        targets.clone(),
+        bot.clone(),
+        limiter.clone(),
+    );
+    info!("Spawning {} targets", targets.snapshot().len());
+    retention::spawn_compaction(cfg.clone(), db.clone(), targets.clone());
+    if cfg.monthly_report() {
+        sla::spawn_monthly_report(cfg.clone(), db.clone(), targets.clone(), bot.clone());
+    }
 
     // —— 启动 Telegram 命令分发 —— //
-    let bot = Bot::new(cfg.token.clone());
-    cmd::cmd_dispatch(bot, cfg, db, targets).await;
+    cmd::cmd_dispatch(bot, cfg, db, targets, limiter).await;
     info!("Dispatcher stopped");
     info!("Starting to Process Telegram Messages");
     Ok(())
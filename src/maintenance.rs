@@ -0,0 +1,266 @@
+// src/maintenance.rs
+use crate::alert::parse_duration;
+use crate::config::TargetConfig;
+use crate::db::Db;
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, NaiveDateTime, NaiveTime, Timelike, Utc};
+use serde::Deserialize;
+
+/// 维护窗口的最长时长（配置中的周期窗口与一次性窗口相同）
+pub const MAX_WINDOW_DAYS: i64 = 7;
+
+/// 周期性维护窗口，例如每周日 03:00 起 30 分钟：
+///
+/// ```toml
+/// maintenance = [{ cron = "0 3 * * 0", duration = "30m" }]
+/// ```
+///
+/// 表达式与时长在加载配置时解析，无效时启动即报错。
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(try_from = "RawWindow")]
+pub struct MaintenanceWindow {
+    /// 窗口开始时间，5 段 cron 表达式（分 时 日 月 周），按本地时区
+    pub cron: String,
+    /// 窗口长度，如 "30m"、"2h"
+    pub duration: String,
+    schedule: Cron,
+    length: Duration,
+}
+
+/// 配置文件中的原始写法
+#[derive(Deserialize)]
+struct RawWindow {
+    cron: String,
+    duration: String,
+}
+
+impl TryFrom<RawWindow> for MaintenanceWindow {
+    type Error = anyhow::Error;
+
+    fn try_from(raw: RawWindow) -> Result<Self> {
+        let schedule = Cron::parse(&raw.cron)?;
+        let length = parse_duration(&raw.duration)?;
+        if length > Duration::days(MAX_WINDOW_DAYS) {
+            return Err(anyhow!("维护时长需在 {}d 以内: {}", MAX_WINDOW_DAYS, raw.duration));
+        }
+        Ok(MaintenanceWindow {
+            cron: raw.cron,
+            duration: raw.duration,
+            schedule,
+            length,
+        })
+    }
+}
+
+impl MaintenanceWindow {
+    /// `at` 是否落在该窗口内
+    pub fn contains(&self, at: DateTime<Utc>) -> bool {
+        self.contains_local(at.with_timezone(&Local).naive_local())
+    }
+
+    /// 向前查找 (at - 时长, at] 内是否有窗口起点，整小时、整天不匹配时直接跳过
+    fn contains_local(&self, at: NaiveDateTime) -> bool {
+        let earliest = at - self.length;
+        let mut t = at.with_second(0).and_then(|t| t.with_nanosecond(0)).unwrap_or(at);
+        while t > earliest {
+            if !self.schedule.matches_day(t.date()) {
+                t = t.date().and_time(NaiveTime::MIN) - Duration::minutes(1);
+            } else if !self.schedule.hour[t.hour() as usize] {
+                t = t.date().and_hms_opt(t.hour(), 0, 0).unwrap_or(t) - Duration::minutes(1);
+            } else if self.schedule.minute[t.minute() as usize] {
+                return true;
+            } else {
+                t -= Duration::minutes(1);
+            }
+        }
+        false
+    }
+}
+
+/// 5 段 cron 表达式，每段支持 `*`、`a`、`a-b`、`*/n`、`a-b/n` 及逗号列表
+#[derive(Debug, Clone, PartialEq)]
+struct Cron {
+    minute: Vec<bool>,
+    hour: Vec<bool>,
+    dom: Vec<bool>,
+    month: Vec<bool>,
+    dow: Vec<bool>,
+    /// 日与周都被限定时按标准 cron 语义取并集
+    dom_any: bool,
+    dow_any: bool,
+}
+
+impl Cron {
+    fn parse(expr: &str) -> Result<Self> {
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        let [minute, hour, dom, month, dow] = fields.as_slice() else {
+            return Err(anyhow!("cron 表达式需要 5 段: {}", expr));
+        };
+        let mut dow_set = parse_field(dow, 0, 7).with_context(|| format!("无效 cron: {}", expr))?;
+        // 0 与 7 都表示周日
+        if dow_set[7] {
+            dow_set[0] = true;
+        }
+        dow_set.truncate(7);
+        Ok(Cron {
+            minute: parse_field(minute, 0, 59).with_context(|| format!("无效 cron: {}", expr))?,
+            hour: parse_field(hour, 0, 23).with_context(|| format!("无效 cron: {}", expr))?,
+            dom: parse_field(dom, 1, 31).with_context(|| format!("无效 cron: {}", expr))?,
+            month: parse_field(month, 1, 12).with_context(|| format!("无效 cron: {}", expr))?,
+            dow: dow_set,
+            dom_any: *dom == "*",
+            dow_any: *dow == "*",
+        })
+    }
+
+    /// 日期是否匹配（月、日、周）
+    fn matches_day(&self, date: NaiveDate) -> bool {
+        let dom = self.dom[date.day() as usize];
+        let dow = self.dow[date.weekday().num_days_from_sunday() as usize];
+        let day = match (self.dom_any, self.dow_any) {
+            (false, false) => dom || dow,
+            _ => dom && dow,
+        };
+        self.month[date.month() as usize] && day
+    }
+}
+
+/// 解析 cron 的一段，返回下标 0..=max 的命中表
+fn parse_field(field: &str, min: u32, max: u32) -> Result<Vec<bool>> {
+    let mut set = vec![false; max as usize + 1];
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((r, s)) => (r, s.parse::<u32>().map_err(|_| anyhow!("无效步长: {}", part))?),
+            None => (part, 1),
+        };
+        if step == 0 {
+            return Err(anyhow!("无效步长: {}", part));
+        }
+        let (lo, hi) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((a, b)) => (a.parse()?, b.parse()?),
+                None => {
+                    let v: u32 = range.parse()?;
+                    (v, v)
+                }
+            },
+        };
+        if lo < min || hi > max || lo > hi {
+            return Err(anyhow!("超出范围 {}-{}: {}", min, max, part));
+        }
+        for v in (lo..=hi).step_by(step as usize) {
+            set[v as usize] = true;
+        }
+    }
+    Ok(set)
+}
+
+/// 目标此刻是否处于维护窗口（配置中的周期窗口或 `/maintenance` 添加的一次性窗口）
+pub async fn in_maintenance(db: &Db, target: &TargetConfig, at: DateTime<Utc>) -> bool {
+    if target.maintenance.iter().flatten().any(|w| w.contains(at)) {
+        return true;
+    }
+    match db.in_maintenance(&target.alias, at).await {
+        Ok(v) => v,
+        Err(e) => {
+            log::error!("读取维护窗口失败 [{}]: {}", target.alias, e);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(cron: &str, duration: &str) -> Result<MaintenanceWindow> {
+        MaintenanceWindow::try_from(RawWindow {
+            cron: cron.to_string(),
+            duration: duration.to_string(),
+        })
+    }
+
+    fn at(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S").unwrap()
+    }
+
+    fn hits(set: &[bool]) -> Vec<usize> {
+        set.iter().enumerate().filter(|(_, &v)| v).map(|(i, _)| i).collect()
+    }
+
+    #[test]
+    fn field_wildcard_range_step_and_list() {
+        assert_eq!(hits(&parse_field("*", 1, 12).unwrap()), (1..=12).collect::<Vec<_>>());
+        assert_eq!(hits(&parse_field("1-3", 0, 59).unwrap()), [1, 2, 3]);
+        assert_eq!(hits(&parse_field("*/15", 0, 59).unwrap()), [0, 15, 30, 45]);
+        assert_eq!(hits(&parse_field("10-20/5", 0, 59).unwrap()), [10, 15, 20]);
+        assert_eq!(hits(&parse_field("1,5,7-8", 0, 23).unwrap()), [1, 5, 7, 8]);
+    }
+
+    #[test]
+    fn invalid_fields_are_rejected() {
+        for field in ["60", "5-1", "*/0", "a", "1-", ""] {
+            assert!(parse_field(field, 0, 59).is_err(), "{}", field);
+        }
+        assert!(parse_field("0", 1, 31).is_err());
+        assert!(Cron::parse("0 3 * *").is_err());
+        assert!(Cron::parse("0 3 * * * *").is_err());
+        assert!(window("0 3 * * 8", "30m").is_err());
+        assert!(window("0 3 * * 0", "abc").is_err());
+        assert!(window("0 3 * * 0", "8d").is_err());
+    }
+
+    #[test]
+    fn day_of_week_sunday_is_0_or_7() {
+        for cron in ["0 3 * * 0", "0 3 * * 7"] {
+            let w = window(cron, "30m").unwrap();
+            // 2026-10-18 是周日
+            assert!(w.contains_local(at("2026-10-18 03:00:00")));
+            assert!(w.contains_local(at("2026-10-18 03:29:59")));
+            assert!(!w.contains_local(at("2026-10-18 03:30:00")));
+            assert!(!w.contains_local(at("2026-10-18 02:59:59")));
+            assert!(!w.contains_local(at("2026-10-19 03:10:00")));
+        }
+    }
+
+    #[test]
+    fn day_of_month_and_week_are_unioned() {
+        // 每月 1 日或每周一
+        let w = window("0 0 1 * 1", "1h").unwrap();
+        assert!(w.contains_local(at("2026-10-01 00:30:00")));
+        assert!(w.contains_local(at("2026-10-19 00:30:00")));
+        assert!(!w.contains_local(at("2026-10-18 00:30:00")));
+        // 只限定周时日为 `*` 不放宽
+        let w = window("0 0 * * 1", "1h").unwrap();
+        assert!(!w.contains_local(at("2026-10-01 00:30:00")));
+    }
+
+    #[test]
+    fn window_spans_midnight_and_months() {
+        let w = window("50 23 * 10 *", "30m").unwrap();
+        assert!(w.contains_local(at("2026-10-17 23:55:00")));
+        assert!(w.contains_local(at("2026-10-18 00:15:00")));
+        assert!(!w.contains_local(at("2026-10-18 00:20:00")));
+        // 10 月 31 日 23:50 开始的窗口延续到 11 月
+        assert!(w.contains_local(at("2026-11-01 00:10:00")));
+        assert!(!w.contains_local(at("2026-11-01 23:55:00")));
+    }
+
+    #[test]
+    fn windows_are_parsed_from_config() {
+        #[derive(Deserialize)]
+        struct Target {
+            maintenance: Vec<MaintenanceWindow>,
+        }
+        let t: Target =
+            toml::from_str(r#"maintenance = [{ cron = "*/30 * * * *", duration = "5m" }]"#)
+                .unwrap();
+        assert!(t.maintenance[0].contains_local(at("2026-10-17 12:34:00")));
+        assert!(!t.maintenance[0].contains_local(at("2026-10-17 12:35:00")));
+        let err = toml::from_str::<Target>(r#"maintenance = [{ cron = "99 * * * *", duration = "5m" }]"#)
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("99"), "{}", err);
+    }
+}
@@ -1,51 +1,412 @@
 // src/monitor.rs
-use crate::{config::Config, db::Db};
-use chrono::Utc;
-use log::debug;
-use std::sync::Arc;
-use std::{
-    net::SocketAddr,
-    time::{Duration, Instant},
+use crate::{
+    alert::{self, Severity},
+    config::{Config, TargetConfig},
+    db::{Db, IncidentUpdate, RoundRecord},
+    maintenance, probe,
+    targets::Targets,
 };
-use tokio::{net::TcpStream, time};
+use chrono::{DateTime, NaiveDate, Utc};
+use log::{debug, info};
+use std::collections::HashMap;
+use std::sync::Arc;
+use teloxide::Bot;
+use tokio::sync::Semaphore;
+use tokio::task::JoinHandle;
+use tokio::time::{self, MissedTickBehavior};
+
+/// 单个目标的在线状态
+#[derive(Debug, Clone, Copy)]
+enum TargetState {
+    Up,
+    /// 自 `since` 起离线
+    Down { since: DateTime<Utc> },
+}
+
+/// 状态切换
+enum Transition {
+    Down { since: DateTime<Utc>, reason: String },
+    Recovered { since: DateTime<Utc> },
+}
+
+/// 带迟滞的告警状态机：连续 N 轮异常才告警，连续 M 轮正常才恢复
+struct Tracker {
+    state: TargetState,
+    /// 当前连续异常轮数及其中第一轮的时间
+    bad_streak: u32,
+    first_bad: Option<DateTime<Utc>>,
+    good_streak: u32,
+}
+
+impl Tracker {
+    fn new() -> Self {
+        Tracker {
+            state: TargetState::Up,
+            bad_streak: 0,
+            first_bad: None,
+            good_streak: 0,
+        }
+    }
+
+    /// 从数据库中未结束的故障接续离线状态（例如重启之后）
+    fn resume(since: DateTime<Utc>) -> Self {
+        Tracker {
+            state: TargetState::Down { since },
+            ..Tracker::new()
+        }
+    }
 
+    fn is_down(&self) -> bool {
+        matches!(self.state, TargetState::Down { .. })
+    }
+
+    /// 输入一轮评估结果，返回可能发生的状态切换
+    fn observe(
+        &mut self,
+        target: &TargetConfig,
+        at: DateTime<Utc>,
+        bad: Option<String>,
+    ) -> Option<Transition> {
+        match bad {
+            Some(reason) => {
+                self.good_streak = 0;
+                self.bad_streak += 1;
+                let first_bad = *self.first_bad.get_or_insert(at);
+                match self.state {
+                    TargetState::Up if self.bad_streak >= target.fail_rounds() => {
+                        self.state = TargetState::Down { since: first_bad };
+                        Some(Transition::Down {
+                            since: first_bad,
+                            reason,
+                        })
+                    }
+                    _ => None,
+                }
+            }
+            None => {
+                self.bad_streak = 0;
+                self.first_bad = None;
+                self.good_streak += 1;
+                match self.state {
+                    TargetState::Down { since }
+                        if self.good_streak >= target.recover_rounds() =>
+                    {
+                        self.state = TargetState::Up;
+                        Some(Transition::Recovered { since })
+                    }
+                    _ => None,
+                }
+            }
+        }
+    }
+}
+
+/// 启动后台监测：每个目标一个独立任务
+///
+/// 目标列表变化时，为新增的目标启动任务，停止已删除目标的任务；
+/// 同名目标配置变化时重启其任务。
 pub fn spawn_monitor(
     cfg: Config,
     db: Arc<Db>, // ← must be Arc<Db>, not Db or Arc<Mutex<...>>
-    targets: Vec<(SocketAddr, String)>,
+    targets: Targets,
+    bot: Bot,
+    limiter: Arc<Semaphore>,
 ) {
+    debug!("Spawning monitor");
+    let mut rx = targets.subscribe();
     tokio::spawn(async move {
-        debug!("Spawning monitor");
-        let interval = Duration::from_secs(cfg.probe_count as u64);
+        let mut running: HashMap<String, (TargetConfig, JoinHandle<()>)> = HashMap::new();
         loop {
-            debug!("Checking interval");
-            let now = Utc::now();
-            for (sock, alias) in &targets {
-                let mut latencies: Vec<f64> = Vec::new();
-                let mut fails = 0;
-                for _ in 0..cfg.probe_count {
-                    let start = Instant::now();
-                    if time::timeout(Duration::from_secs(1), TcpStream::connect(sock))
-                        .await
-                        .is_err()
-                    {
-                        fails += 1;
-                    } else {
-                        latencies.push(start.elapsed().as_millis() as f64);
-                    }
+            let current = rx.borrow_and_update().clone();
+            running.retain(|alias, (old, handle)| {
+                let keep = current.iter().any(|t| t == old);
+                if !keep {
+                    info!("[{}] 停止监测", alias);
+                    handle.abort();
+                }
+                keep
+            });
+            for target in current {
+                if running.contains_key(&target.alias) {
+                    continue;
+                }
+                info!("[{}] 开始监测 ({:?} {})", target.alias, target.kind, target.address);
+                let handle = tokio::spawn(watch_target(
+                    cfg.clone(),
+                    db.clone(),
+                    target.clone(),
+                    bot.clone(),
+                    limiter.clone(),
+                ));
+                running.insert(target.alias.clone(), (target, handle));
+            }
+            if rx.changed().await.is_err() {
+                break;
+            }
+        }
+    });
+}
+
+/// 单个目标的监测循环，按目标自身的间隔独立调度
+///
+/// 所有目标共用 `limiter`，同一时刻最多 `concurrency` 个目标在探测。
+async fn watch_target(
+    cfg: Config,
+    db: Arc<Db>,
+    target: TargetConfig,
+    bot: Bot,
+    limiter: Arc<Semaphore>,
+) {
+    let alias = &target.alias;
+    let prober = match probe::prober(&target, target.timeout(&cfg)) {
+        Ok(prober) => prober,
+        Err(e) => {
+            log::error!("[{}] 无法探测: {:#}", alias, e);
+            return;
+        }
+    };
+    let attempts = target.attempts(&cfg);
+    let mut ticker = time::interval(target.interval(&cfg));
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    // 当前进行中的故障记录
+    let mut incident: Option<i64> = None;
+    let mut tracker = match db.ongoing_incident(alias).await {
+        Ok(Some((id, since))) => {
+            info!("[{}] 接续未结束的故障 #{}", alias, id);
+            incident = Some(id);
+            Tracker::resume(since)
+        }
+        Ok(None) => Tracker::new(),
+        Err(e) => {
+            log::error!("读取故障记录失败 [{}]: {}", alias, e);
+            Tracker::new()
+        }
+    };
+    // 证书预警每天最多推送一次
+    let mut cert_warned: Option<NaiveDate> = None;
+    // DNS 目标上一次的解析结果
+    let mut dns_answers: Option<Vec<String>> = None;
+    loop {
+        ticker.tick().await;
+        debug!("[{}] Checking interval", alias);
+        let result = {
+            let _permit = limiter.acquire().await.expect("limiter closed");
+            prober.probe(attempts).await
+        };
+        // 以实际开始探测的时间入库，而不是排队前的时间
+        let now = result.started_at().unwrap_or_else(Utc::now);
+        let stats = result.stats();
+        let (avg, loss) = (stats.avg.unwrap_or(0.0), stats.loss);
+
+        // 维护窗口内照常记录，但标记出来并且不发离线/恢复告警
+        let maintenance = maintenance::in_maintenance(&db, &target, now).await;
+
+        let mut round = RoundRecord {
+            alias: alias.clone(),
+            ts: now,
+            stats,
+            timings: result.timings(),
+            maintenance,
+            not_after: result.not_after,
+            incident: None,
+        };
+
+        // —— 证书到期预警 —— //
+        if let Some(not_after) = result.not_after {
+            let days_left = (not_after - now).num_days();
+            let today = now.date_naive();
+            if days_left <= target.cert_warn_days() && cert_warned != Some(today) {
+                cert_warned = Some(today);
+                let text = format!(
+                    "⚠️ [{}] TLS 证书将在 {} 天后过期\n到期时间：{}",
+                    alias,
+                    days_left,
+                    not_after.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S")
+                );
+                alert::broadcast(&bot, &db, &target, Severity::Cert, &text).await;
+            }
+        }
+
+        // —— DNS 解析结果变化 —— //
+        if let Some(answers) = &result.answers {
+            if let Some(prev) = dns_answers.replace(answers.clone()) {
+                if prev != *answers && !maintenance {
+                    info!("[{}] DNS 解析结果变化: {:?} → {:?}", alias, prev, answers);
+                    let text = format!(
+                        "🔄 [{}] DNS 解析结果变化 ({})\n原：{}\n现：{}",
+                        alias,
+                        target.record(),
+                        prev.join(", "),
+                        answers.join(", ")
+                    );
+                    alert::broadcast(&bot, &db, &target, Severity::Degraded, &text).await;
                 }
-                let avg = if latencies.is_empty() {
-                    0.0
+            }
+        }
+
+        // —— 按阈值评估 —— //
+        let transition = if maintenance {
+            debug!("[{}] 维护中，跳过告警评估", alias);
+            None
+        } else {
+            let bad = target.evaluate(avg, loss);
+            let was_bad = bad.is_some();
+            let transition = tracker.observe(&target, now, bad);
+            round.incident = match &transition {
+                Some(Transition::Down { since, reason }) => Some(IncidentUpdate::Open {
+                    start: *since,
+                    cause: result
+                        .last_error()
+                        .map_or("latency", |err| err.kind.as_str())
+                        .to_owned(),
+                    reason: reason.clone(),
+                    loss,
+                }),
+                Some(Transition::Recovered { .. }) => incident
+                    .take()
+                    .map(|id| IncidentUpdate::Close { id, end: now }),
+                None => incident
+                    .filter(|_| was_bad && tracker.is_down())
+                    .map(|id| IncidentUpdate::Peak { id, loss }),
+            };
+            transition
+        };
+
+        // 本轮的 metrics、证书与故障记录在一个事务中写入
+        match db.record_round(round).await {
+            Ok(Some(id)) => incident = Some(id),
+            Ok(None) => {}
+            Err(e) => log::error!("写入探测结果失败 [{}]: {}", alias, e),
+        }
+
+        // —— 状态切换时推送告警 —— //
+        match transition {
+            Some(Transition::Down { since, mut reason }) => {
+                if let Some(err) = result.last_error() {
+                    reason.push_str(&format!("（{}：{}）", err.kind.label(), err));
+                }
+                // 全部丢包为离线，其余（部分丢包、延迟超限）为性能下降
+                let (severity, title) = if loss >= 100.0 {
+                    (Severity::Down, format!("🔴 [{}] 离线 (DOWN)", alias))
                 } else {
-                    latencies.iter().sum::<f64>() / latencies.len() as f64
+                    (Severity::Degraded, format!("🟠 [{}] 性能下降 (DEGRADED)", alias))
                 };
-                let loss = fails as f64 / (cfg.probe_count as f64) * 100.0;
+                info!("[{}] {:?}: {}", alias, severity, reason);
+                let text = format!(
+                    "{}\n原因：{}\n开始时间：{}",
+                    title,
+                    reason,
+                    since.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S")
+                );
+                alert::broadcast(&bot, &db, &target, severity, &text).await;
+            }
+            Some(Transition::Recovered { since }) => {
+                info!("[{}] RECOVERED", alias);
+                let text = format!(
+                    "🟢 [{}] 已恢复 (RECOVERED)\n中断时长：{}",
+                    alias,
+                    alert::format_duration(now - since)
+                );
+                alert::broadcast(&bot, &db, &target, Severity::Recovered, &text).await;
+            }
+            None => {}
+        }
+    }
+}
 
-                if let Err(e) = db.insert_metric(alias, now, avg, loss).await {
-                    log::error!("写入 metrics 失败 [{}]: {}", alias, e);
-                }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ProbeKind;
+    use chrono::{Duration, TimeZone};
+
+    fn target(fail_rounds: u32, recover_rounds: u32) -> TargetConfig {
+        TargetConfig {
+            fail_rounds: Some(fail_rounds),
+            recover_rounds: Some(recover_rounds),
+            ..TargetConfig::new("hk", "1.2.3.4:443", ProbeKind::Tcp)
+        }
+    }
+
+    /// 第 `i` 轮的时间
+    fn at(i: i64) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 10, 1, 0, 0, 0).unwrap() + Duration::minutes(i)
+    }
+
+    fn bad() -> Option<String> {
+        Some(String::from("丢包率 100.0%"))
+    }
+
+    #[test]
+    fn goes_down_after_consecutive_bad_rounds() {
+        let target = target(3, 1);
+        let mut tracker = Tracker::new();
+        assert!(tracker.observe(&target, at(0), bad()).is_none());
+        assert!(tracker.observe(&target, at(1), bad()).is_none());
+        match tracker.observe(&target, at(2), bad()) {
+            Some(Transition::Down { since, reason }) => {
+                assert_eq!(since, at(0), "离线时间取连续异常的第一轮");
+                assert_eq!(reason, "丢包率 100.0%");
             }
-            time::sleep(interval).await;
+            _ => panic!("第 3 轮异常应切换为离线"),
         }
-    });
+        assert!(tracker.is_down());
+        // 已离线时继续异常不再重复告警
+        assert!(tracker.observe(&target, at(3), bad()).is_none());
+    }
+
+    #[test]
+    fn recovers_after_consecutive_good_rounds() {
+        let target = target(1, 2);
+        let mut tracker = Tracker::new();
+        assert!(matches!(
+            tracker.observe(&target, at(0), bad()),
+            Some(Transition::Down { .. })
+        ));
+        assert!(tracker.observe(&target, at(1), None).is_none());
+        assert!(tracker.is_down());
+        match tracker.observe(&target, at(2), None) {
+            Some(Transition::Recovered { since }) => assert_eq!(since, at(0)),
+            _ => panic!("连续 2 轮正常应恢复"),
+        }
+        assert!(!tracker.is_down());
+        assert!(tracker.observe(&target, at(3), None).is_none());
+    }
+
+    #[test]
+    fn streaks_reset_when_result_flips() {
+        let target = target(2, 2);
+        let mut tracker = Tracker::new();
+        // 异常、正常交替，始终达不到连续 2 轮
+        for i in 0..6 {
+            let result = if i % 2 == 0 { bad() } else { None };
+            assert!(tracker.observe(&target, at(i), result).is_none());
+        }
+        assert!(!tracker.is_down());
+
+        // 中断后重新计数，离线时间取新一轮连续异常的第一轮
+        assert!(tracker.observe(&target, at(6), bad()).is_none());
+        assert!(matches!(
+            tracker.observe(&target, at(7), bad()),
+            Some(Transition::Down { since, .. }) if since == at(6)
+        ));
+        assert!(tracker.observe(&target, at(8), None).is_none());
+        assert!(tracker.observe(&target, at(9), bad()).is_none());
+        assert!(tracker.observe(&target, at(10), None).is_none());
+        assert!(tracker.is_down(), "恢复所需的连续正常轮数被异常打断");
+    }
+
+    #[test]
+    fn resume_continues_ongoing_incident() {
+        let target = target(1, 1);
+        let mut tracker = Tracker::resume(at(0));
+        assert!(tracker.is_down());
+        // 接续后继续异常不会再开一次故障
+        assert!(tracker.observe(&target, at(5), bad()).is_none());
+        match tracker.observe(&target, at(6), None) {
+            Some(Transition::Recovered { since }) => assert_eq!(since, at(0)),
+            _ => panic!("接续的故障应按原起始时间恢复"),
+        }
+    }
 }
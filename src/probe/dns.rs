@@ -0,0 +1,106 @@
+// src/probe/dns.rs
+use super::{elapsed_ms, fail, run_attempts, ErrorKind, ProbeResult, Prober, Sample};
+use crate::config::TargetConfig;
+use anyhow::{anyhow, Context, Result};
+use futures::future::BoxFuture;
+use hickory_resolver::config::{NameServerConfig, ResolverConfig, ResolverOpts};
+use hickory_resolver::net::runtime::TokioRuntimeProvider;
+use hickory_resolver::proto::rr::RecordType;
+use hickory_resolver::{Resolver, TokioResolver};
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+/// 默认 DNS 探测的超时时间
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 一次成功的 DNS 解析
+#[derive(Debug, Clone)]
+pub struct DnsOutcome {
+    /// 解析耗时 (ms)
+    pub elapsed: f64,
+    /// 排序后的应答记录，用于比较解析结果是否变化
+    pub answers: Vec<String>,
+}
+
+/// DNS 解析探测器
+pub struct DnsProber {
+    target: TargetConfig,
+    timeout: Duration,
+}
+
+impl DnsProber {
+    pub fn new(target: &TargetConfig, timeout: Duration) -> Self {
+        DnsProber {
+            target: target.clone(),
+            timeout,
+        }
+    }
+}
+
+impl Prober for DnsProber {
+    fn probe(&self, attempts: usize) -> BoxFuture<'_, ProbeResult> {
+        let (target, timeout) = (&self.target, self.timeout);
+        Box::pin(run_attempts(&target.alias, attempts, move || async move {
+            let out = probe_dns(target, timeout).await?;
+            Ok(Sample {
+                rtt: out.elapsed,
+                answers: Some(out.answers),
+                ..Default::default()
+            })
+        }))
+    }
+}
+
+/// 向 `target.resolver`（缺省为系统解析器）查询 `target.address` 的 `target.record` 记录
+///
+/// 查询失败或没有对应类型的应答都视为探测失败。
+pub async fn probe_dns(target: &TargetConfig, timeout: Duration) -> Result<DnsOutcome> {
+    let record = RecordType::from_str(&target.record().to_ascii_uppercase())
+        .map_err(|_| anyhow!("未知记录类型: {}", target.record()))?;
+    let resolver = build_resolver(target.resolver.as_deref(), timeout)?;
+
+    let start = Instant::now();
+    let lookup = tokio::time::timeout(timeout, resolver.lookup(target.address.as_str(), record))
+        .await
+        .map_err(|_| fail(ErrorKind::Timeout, "超时"))??;
+    let elapsed = elapsed_ms(start);
+
+    let mut answers: Vec<String> = lookup
+        .answers()
+        .iter()
+        .filter(|r| r.record_type() == record)
+        .map(|r| r.data.to_string())
+        .collect();
+    if answers.is_empty() {
+        return Err(fail(ErrorKind::Dns, format!("没有 {} 记录", record)));
+    }
+    answers.sort();
+    answers.dedup();
+    Ok(DnsOutcome { elapsed, answers })
+}
+
+/// 构造不带缓存的解析器，保证每次探测都真正发出查询
+fn build_resolver(server: Option<&str>, timeout: Duration) -> Result<TokioResolver> {
+    let mut builder = match server {
+        Some(server) => {
+            let addr = SocketAddr::from_str(server)
+                .or_else(|_| IpAddr::from_str(server).map(|ip| SocketAddr::new(ip, 53)))
+                .with_context(|| format!("无效解析器地址: {}", server))?;
+            let mut ns = NameServerConfig::udp_and_tcp(addr.ip());
+            for conn in &mut ns.connections {
+                conn.port = addr.port();
+            }
+            Resolver::builder_with_config(
+                ResolverConfig::from_name_servers(vec![ns]),
+                TokioRuntimeProvider::default(),
+            )
+        }
+        None => Resolver::builder_tokio()?,
+    };
+    let opts: &mut ResolverOpts = builder.options_mut();
+    opts.cache_size = 0;
+    opts.timeout = timeout;
+    opts.attempts = 0;
+    Ok(builder.build()?)
+}
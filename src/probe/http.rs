@@ -0,0 +1,248 @@
+// src/probe/http.rs
+use super::{
+    elapsed_ms, fail, run_attempts, tls_connector, ErrorKind, PhaseTimings, ProbeResult, Prober,
+    Sample,
+};
+use crate::config::TargetConfig;
+use anyhow::{anyhow, Context, Result};
+use futures::future::BoxFuture;
+use http_body_util::{BodyExt, Empty};
+use hyper::body::Bytes;
+use hyper::client::conn::http1;
+use hyper::header::{ACCEPT, CONNECTION, HOST, USER_AGENT};
+use hyper::{Request, StatusCode};
+use hyper_util::rt::TokioIo;
+use regex::Regex;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{lookup_host, TcpStream};
+use tokio::task::JoinHandle;
+use tokio_rustls::rustls::pki_types::ServerName;
+
+/// 默认 HTTP 探测的超时时间（含 DNS、建连、TLS 及读取响应体）
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 一次成功的 HTTP 探测
+#[derive(Debug, Clone, Copy)]
+pub struct HttpOutcome {
+    /// 从发出请求到读完响应体的耗时 (ms)
+    pub total: f64,
+    pub timings: PhaseTimings,
+}
+
+/// HTTP(S) 探测器
+pub struct HttpProber {
+    target: TargetConfig,
+    /// 编译好的 `body_regex`
+    body_regex: Option<Regex>,
+    timeout: Duration,
+}
+
+impl HttpProber {
+    /// `body_regex` 无效时返回错误
+    pub fn new(target: &TargetConfig, timeout: Duration) -> Result<Self> {
+        let body_regex = target
+            .body_regex
+            .as_deref()
+            .map(Regex::new)
+            .transpose()
+            .context("body_regex 无效")?;
+        Ok(HttpProber {
+            target: target.clone(),
+            body_regex,
+            timeout,
+        })
+    }
+}
+
+impl Prober for HttpProber {
+    fn probe(&self, attempts: usize) -> BoxFuture<'_, ProbeResult> {
+        let (target, timeout) = (&self.target, self.timeout);
+        let body_regex = self.body_regex.as_ref();
+        Box::pin(run_attempts(&target.alias, attempts, move || async move {
+            let out = probe_http(target, body_regex, timeout).await?;
+            Ok(Sample {
+                rtt: out.total,
+                timings: out.timings,
+                ..Default::default()
+            })
+        }))
+    }
+}
+
+/// 对 `target.address` 指向的 URL 做一次 HTTP(S) 探测
+///
+/// DNS / TCP / TLS 三个阶段逐步计时，随后在同一条连接上用 HTTP/1.1 发出请求，
+/// 每次探测只建立一条连接；TTFB 为请求发出到收到响应头的耗时，
+/// 总耗时为请求发出到读完响应体。不跟随重定向，以便 `expect_status` 能匹配 3xx。
+/// 状态码不在期望范围内或响应体不匹配 `body_regex` 时返回错误。
+pub async fn probe_http(
+    target: &TargetConfig,
+    body_regex: Option<&Regex>,
+    timeout: Duration,
+) -> Result<HttpOutcome> {
+    tokio::time::timeout(timeout, probe_inner(target, body_regex))
+        .await
+        .map_err(|_| fail(ErrorKind::Timeout, "超时"))?
+}
+
+async fn probe_inner(target: &TargetConfig, body_regex: Option<&Regex>) -> Result<HttpOutcome> {
+    let url = reqwest::Url::parse(&target.address)
+        .with_context(|| format!("无效 URL: {}", target.address))?;
+    // Host 头沿用 URL 中的写法（IPv6 带方括号），解析与 SNI 使用去掉方括号的主机名
+    let host = url
+        .host_str()
+        .ok_or_else(|| anyhow!("URL 缺少主机名: {}", url))?
+        .to_string();
+    let name = host.trim_start_matches('[').trim_end_matches(']').to_string();
+    let port = url
+        .port_or_known_default()
+        .ok_or_else(|| anyhow!("URL 缺少端口: {}", url))?;
+
+    // 1. DNS
+    let start = Instant::now();
+    let addr = lookup_host((name.as_str(), port))
+        .await
+        .map_err(|e| fail(ErrorKind::Dns, format!("DNS 解析失败: {}: {}", host, e)))?
+        .next()
+        .ok_or_else(|| fail(ErrorKind::Dns, format!("DNS 无结果: {}", host)))?;
+    let dns = elapsed_ms(start);
+
+    // 2. TCP
+    let start = Instant::now();
+    let stream = TcpStream::connect(addr).await?;
+    let connect = elapsed_ms(start);
+
+    // 3. TLS，之后的请求复用这条连接
+    let (tls, exchange) = if url.scheme() == "https" {
+        let name = ServerName::try_from(name)?;
+        let start = Instant::now();
+        let stream = tls_connector().connect(name, stream).await?;
+        (Some(elapsed_ms(start)), get(stream, &url, &host).await?)
+    } else {
+        (None, get(stream, &url, &host).await?)
+    };
+    let Exchange {
+        status,
+        ttfb,
+        body,
+        total,
+    } = exchange;
+
+    let status_ok = match &target.expect_status {
+        Some(codes) => codes.contains(&status.as_u16()),
+        None => status.is_success() || status.is_redirection(),
+    };
+    if !status_ok {
+        return Err(fail(ErrorKind::HttpStatus, format!("HTTP {}", status)));
+    }
+    if let Some(re) = body_regex {
+        if !re.is_match(&String::from_utf8_lossy(&body)) {
+            return Err(fail(
+                ErrorKind::Mismatch,
+                format!("响应体不匹配 /{}/", re.as_str()),
+            ));
+        }
+    }
+
+    Ok(HttpOutcome {
+        total,
+        timings: PhaseTimings {
+            dns: Some(dns),
+            connect: Some(connect),
+            tls,
+            ttfb: Some(ttfb),
+        },
+    })
+}
+
+/// 一次请求的结果
+struct Exchange {
+    status: StatusCode,
+    /// 请求发出到收到响应头 (ms)
+    ttfb: f64,
+    body: Bytes,
+    /// 请求发出到读完响应体 (ms)
+    total: f64,
+}
+
+/// 任务句柄被丢弃时终止任务，避免探测超时后连接仍在后台读写
+struct AbortOnDrop(JoinHandle<()>);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// 在已建立（并已计时）的连接上发出 GET 请求并读完响应
+async fn get<S>(stream: S, url: &reqwest::Url, host: &str) -> Result<Exchange>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let (mut sender, conn) = http1::handshake(TokioIo::new(stream)).await?;
+    let _conn = AbortOnDrop(tokio::spawn(async move {
+        let _ = conn.await;
+    }));
+
+    let authority = match url.port() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host.to_string(),
+    };
+    let path = match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_string(),
+    };
+    let request = Request::get(path)
+        .header(HOST, authority)
+        .header(USER_AGENT, concat!("tg_prober/", env!("CARGO_PKG_VERSION")))
+        .header(ACCEPT, "*/*")
+        .header(CONNECTION, "close")
+        .body(Empty::<Bytes>::new())?;
+
+    let start = Instant::now();
+    let resp = sender.send_request(request).await?;
+    let ttfb = elapsed_ms(start);
+    let status = resp.status();
+    let body = resp.into_body().collect().await?.to_bytes();
+    Ok(Exchange {
+        status,
+        ttfb,
+        body,
+        total: elapsed_ms(start),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ProbeKind;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn probes_ipv6_literal() {
+        let listener = TcpListener::bind("[::1]:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0u8; 1024];
+            let n = stream.read(&mut buf).await.unwrap();
+            let request = String::from_utf8_lossy(&buf[..n]).to_string();
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok")
+                .await
+                .unwrap();
+            request
+        });
+
+        let address = format!("http://[::1]:{}/health", port);
+        let target = TargetConfig::new("v6", &address, ProbeKind::Http);
+        let out = probe_http(&target, None, DEFAULT_TIMEOUT).await.unwrap();
+        assert!(out.timings.dns.is_some() && out.timings.ttfb.is_some());
+
+        let request = server.await.unwrap();
+        assert!(request.starts_with("GET /health HTTP/1.1\r\n"));
+        assert!(request.contains(&format!("host: [::1]:{}\r\n", port)));
+    }
+}
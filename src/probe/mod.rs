@@ -0,0 +1,519 @@
+// src/probe/mod.rs
+pub mod dns;
+pub mod http;
+pub mod tcp;
+pub mod tls;
+pub mod udp;
+
+use crate::config::{ProbeKind, TargetConfig};
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use log::debug;
+use once_cell::sync::Lazy;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_rustls::rustls::{crypto::ring, ClientConfig, RootCertStore};
+use tokio_rustls::TlsConnector;
+
+/// 单次探测的分阶段耗时 (ms)，仅 HTTP / TLS 探测会填充
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PhaseTimings {
+    pub dns: Option<f64>,
+    pub connect: Option<f64>,
+    pub tls: Option<f64>,
+    pub ttfb: Option<f64>,
+}
+
+impl PhaseTimings {
+    /// 对多次成功探测的各阶段耗时分别取平均
+    pub fn mean(samples: &[PhaseTimings]) -> PhaseTimings {
+        fn avg(vals: impl Iterator<Item = Option<f64>>) -> Option<f64> {
+            let vals: Vec<f64> = vals.flatten().collect();
+            if vals.is_empty() {
+                None
+            } else {
+                Some(vals.iter().sum::<f64>() / vals.len() as f64)
+            }
+        }
+        PhaseTimings {
+            dns: avg(samples.iter().map(|t| t.dns)),
+            connect: avg(samples.iter().map(|t| t.connect)),
+            tls: avg(samples.iter().map(|t| t.tls)),
+            ttfb: avg(samples.iter().map(|t| t.ttfb)),
+        }
+    }
+}
+
+/// 探测失败的类别
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    Timeout,
+    Refused,
+    Dns,
+    Tls,
+    HttpStatus,
+    Mismatch,
+    Other,
+}
+
+impl ErrorKind {
+    /// 稳定的英文标识，用于日志与存储
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorKind::Timeout => "timeout",
+            ErrorKind::Refused => "refused",
+            ErrorKind::Dns => "dns",
+            ErrorKind::Tls => "tls",
+            ErrorKind::HttpStatus => "http_status",
+            ErrorKind::Mismatch => "mismatch",
+            ErrorKind::Other => "other",
+        }
+    }
+
+    /// 面向用户的中文描述
+    pub fn label(&self) -> &'static str {
+        match self {
+            ErrorKind::Timeout => "超时",
+            ErrorKind::Refused => "连接被拒绝",
+            ErrorKind::Dns => "解析失败",
+            ErrorKind::Tls => "TLS 错误",
+            ErrorKind::HttpStatus => "HTTP 状态码异常",
+            ErrorKind::Mismatch => "应答不匹配",
+            ErrorKind::Other => "其他错误",
+        }
+    }
+}
+
+/// 带类别的探测失败
+#[derive(Debug, Clone)]
+pub struct Failure {
+    pub kind: ErrorKind,
+    pub message: String,
+}
+
+impl Failure {
+    /// 从任意错误归类：优先取探测代码显式给出的类别，其次识别底层 IO / TLS / DNS 错误
+    fn classify(err: &anyhow::Error) -> Failure {
+        let kind = err
+            .chain()
+            .find_map(|cause| {
+                if let Some(f) = cause.downcast_ref::<Failure>() {
+                    return Some(f.kind);
+                }
+                if cause.is::<tokio_rustls::rustls::Error>() {
+                    return Some(ErrorKind::Tls);
+                }
+                if cause.is::<hickory_resolver::net::NetError>() {
+                    return Some(ErrorKind::Dns);
+                }
+                if let Some(e) = cause.downcast_ref::<hyper::Error>() {
+                    if e.is_timeout() {
+                        return Some(ErrorKind::Timeout);
+                    }
+                }
+                let io = cause.downcast_ref::<std::io::Error>()?;
+                match io.kind() {
+                    std::io::ErrorKind::ConnectionRefused => Some(ErrorKind::Refused),
+                    std::io::ErrorKind::TimedOut => Some(ErrorKind::Timeout),
+                    _ if io
+                        .get_ref()
+                        .is_some_and(|inner| inner.is::<tokio_rustls::rustls::Error>()) =>
+                    {
+                        Some(ErrorKind::Tls)
+                    }
+                    _ => None,
+                }
+            })
+            .unwrap_or(ErrorKind::Other);
+        Failure {
+            kind,
+            message: format!("{:#}", err),
+        }
+    }
+}
+
+impl std::fmt::Display for Failure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Failure {}
+
+/// 构造带类别的错误
+pub(crate) fn fail(kind: ErrorKind, message: impl Into<String>) -> anyhow::Error {
+    Failure {
+        kind,
+        message: message.into(),
+    }
+    .into()
+}
+
+/// 一轮探测的延迟统计 (ms)；没有成功探测时除 `loss` 外均为 `None`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RoundStats {
+    pub avg: Option<f64>,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub p50: Option<f64>,
+    pub p95: Option<f64>,
+    /// 标准差
+    pub stddev: Option<f64>,
+    /// 相邻两次（依次进行的）探测耗时差的绝对值的平均
+    pub jitter: Option<f64>,
+    /// 丢包率 (%)
+    pub loss: f64,
+}
+
+/// 最近秩法求百分位，`sorted` 须已升序
+fn percentile(sorted: &[f64], q: f64) -> f64 {
+    let rank = (q * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// 单次探测记录
+#[derive(Debug, Clone)]
+pub struct Attempt {
+    /// 开始探测的时间
+    pub at: DateTime<Utc>,
+    /// 成功时的耗时 (ms)
+    pub rtt: Option<f64>,
+    pub error: Option<Failure>,
+    pub timings: PhaseTimings,
+}
+
+/// 一轮探测的完整结果
+#[derive(Debug, Clone, Default)]
+pub struct ProbeResult {
+    pub attempts: Vec<Attempt>,
+    /// TLS：对端证书的 notAfter
+    pub not_after: Option<DateTime<Utc>>,
+    /// DNS：最近一次的应答记录
+    pub answers: Option<Vec<String>>,
+}
+
+impl ProbeResult {
+    /// 每次成功探测的耗时 (ms)
+    pub fn latencies(&self) -> Vec<f64> {
+        self.attempts.iter().filter_map(|a| a.rtt).collect()
+    }
+
+    pub fn successes(&self) -> usize {
+        self.attempts.iter().filter(|a| a.rtt.is_some()).count()
+    }
+
+    /// 丢包率 (%)
+    pub fn loss(&self) -> f64 {
+        if self.attempts.is_empty() {
+            0.0
+        } else {
+            (self.attempts.len() - self.successes()) as f64 / self.attempts.len() as f64 * 100.0
+        }
+    }
+
+    /// 本轮的延迟分布统计
+    pub fn stats(&self) -> RoundStats {
+        let lat = self.latencies();
+        let loss = self.loss();
+        if lat.is_empty() {
+            return RoundStats {
+                loss,
+                ..Default::default()
+            };
+        }
+        let n = lat.len() as f64;
+        let avg = lat.iter().sum::<f64>() / n;
+        let stddev = (lat.iter().map(|l| (l - avg).powi(2)).sum::<f64>() / n).sqrt();
+        let jitter = if lat.len() > 1 {
+            lat.windows(2).map(|w| (w[1] - w[0]).abs()).sum::<f64>() / (n - 1.0)
+        } else {
+            0.0
+        };
+        let mut sorted = lat;
+        sorted.sort_by(f64::total_cmp);
+        RoundStats {
+            avg: Some(avg),
+            min: sorted.first().copied(),
+            max: sorted.last().copied(),
+            p50: Some(percentile(&sorted, 0.5)),
+            p95: Some(percentile(&sorted, 0.95)),
+            stddev: Some(stddev),
+            jitter: Some(jitter),
+            loss,
+        }
+    }
+
+    /// 成功探测的分阶段耗时均值
+    pub fn timings(&self) -> PhaseTimings {
+        let samples: Vec<PhaseTimings> = self
+            .attempts
+            .iter()
+            .filter(|a| a.rtt.is_some())
+            .map(|a| a.timings)
+            .collect();
+        PhaseTimings::mean(&samples)
+    }
+
+    /// 本轮第一次探测的开始时间
+    pub fn started_at(&self) -> Option<DateTime<Utc>> {
+        self.attempts.first().map(|a| a.at)
+    }
+
+    /// 最近一次失败
+    pub fn last_error(&self) -> Option<&Failure> {
+        self.attempts.iter().rev().find_map(|a| a.error.as_ref())
+    }
+}
+
+/// 探测器：每种探测类型实现一次，后台监测与 `/isonline` 共用
+pub trait Prober: Send + Sync {
+    /// 执行一轮探测，`attempts` 次尝试依次进行
+    fn probe(&self, attempts: usize) -> BoxFuture<'_, ProbeResult>;
+}
+
+/// 按目标类型构造探测器，`timeout` 为单次探测的超时
+///
+/// 正则等探测参数在这里编译，无效时返回错误；配置文件中的目标已在加载时检查过。
+pub fn prober(target: &TargetConfig, timeout: Duration) -> Result<Box<dyn Prober>> {
+    Ok(match target.kind {
+        ProbeKind::Tcp => Box::new(tcp::TcpProber::new(target, timeout)),
+        ProbeKind::Udp => Box::new(udp::UdpProber::new(target, timeout)?),
+        ProbeKind::Http => Box::new(http::HttpProber::new(target, timeout)?),
+        ProbeKind::Tls => Box::new(tls::TlsProber::new(target, timeout)),
+        ProbeKind::Dns => Box::new(dns::DnsProber::new(target, timeout)),
+    })
+}
+
+/// 检查目标的地址与探测参数，用于加载配置文件时提前发现错误
+pub fn validate(target: &TargetConfig) -> Result<()> {
+    validate_address(target.kind, &target.address)?;
+    prober(target, default_timeout(target.kind)).map(|_| ())
+}
+
+/// 各探测类型的默认超时
+pub fn default_timeout(kind: ProbeKind) -> Duration {
+    match kind {
+        ProbeKind::Tcp => tcp::DEFAULT_TIMEOUT,
+        ProbeKind::Udp => udp::DEFAULT_TIMEOUT,
+        ProbeKind::Http => http::DEFAULT_TIMEOUT,
+        ProbeKind::Tls => tls::DEFAULT_TIMEOUT,
+        ProbeKind::Dns => dns::DEFAULT_TIMEOUT,
+    }
+}
+
+/// 单次成功探测
+#[derive(Debug, Default)]
+pub(crate) struct Sample {
+    pub rtt: f64,
+    pub timings: PhaseTimings,
+    pub not_after: Option<DateTime<Utc>>,
+    pub answers: Option<Vec<String>>,
+}
+
+impl Sample {
+    pub fn rtt(rtt: f64) -> Self {
+        Sample {
+            rtt,
+            ..Default::default()
+        }
+    }
+}
+
+/// 依次执行 `n` 次 `once`，记录每次的实际开始时间、耗时或失败原因
+///
+/// 逐次进行而不是并发，`jitter` 才是相邻两次探测之间的延迟变化；
+/// 一轮最长耗时约为 `n` 倍超时。
+pub(crate) async fn run_attempts<F, Fut>(alias: &str, n: usize, once: F) -> ProbeResult
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<Sample>>,
+{
+    let mut result = ProbeResult::default();
+    for _ in 0..n {
+        let at = Utc::now();
+        match once().await {
+            Ok(s) => {
+                result.not_after = s.not_after.or(result.not_after);
+                result.answers = s.answers.or(result.answers.take());
+                result.attempts.push(Attempt {
+                    at,
+                    rtt: Some(s.rtt),
+                    error: None,
+                    timings: s.timings,
+                });
+            }
+            Err(e) => {
+                let failure = Failure::classify(&e);
+                debug!("[{}] 探测失败 ({}): {}", alias, failure.kind.as_str(), failure);
+                result.attempts.push(Attempt {
+                    at,
+                    rtt: None,
+                    error: Some(failure),
+                    timings: PhaseTimings::default(),
+                });
+            }
+        }
+    }
+    result
+}
+
+/// 整轮失败（如地址解析失败），`n` 次探测都记为同一原因
+pub(crate) fn failed_round(alias: &str, n: usize, err: &anyhow::Error) -> ProbeResult {
+    debug!("[{}] {:#}", alias, err);
+    let at = Utc::now();
+    let error = Failure::classify(err);
+    ProbeResult {
+        attempts: (0..n)
+            .map(|_| Attempt {
+                at,
+                rtt: None,
+                error: Some(error.clone()),
+                timings: PhaseTimings::default(),
+            })
+            .collect(),
+        ..Default::default()
+    }
+}
+
+/// 从 `Instant` 起算的毫秒数
+pub(crate) fn elapsed_ms(start: std::time::Instant) -> f64 {
+    start.elapsed().as_secs_f64() * 1000.0
+}
+
+/// 把 `host:port` 或 `[v6]:port` 拆成主机名与端口
+pub(crate) fn split_host_port(addr: &str) -> Result<(String, u16)> {
+    let (host, port) = addr
+        .rsplit_once(':')
+        .ok_or_else(|| fail(ErrorKind::Other, format!("地址缺少端口: {}", addr)))?;
+    let port = port
+        .parse()
+        .with_context(|| format!("无效端口: {}", addr))?;
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    Ok((host.to_string(), port))
+}
+
+/// 检查地址格式是否适用于该探测类型，用于运行期添加目标与加载配置文件
+pub fn validate_address(kind: ProbeKind, address: &str) -> Result<()> {
+    match kind {
+        ProbeKind::Tcp | ProbeKind::Tls | ProbeKind::Udp => {
+            let (host, _) = split_host_port(address)?;
+            if host.is_empty() {
+                return Err(anyhow!("地址缺少主机名: {}", address));
+            }
+        }
+        ProbeKind::Http => {
+            let url = reqwest::Url::parse(address)
+                .with_context(|| format!("无效 URL: {}", address))?;
+            if !matches!(url.scheme(), "http" | "https") || url.host_str().is_none() {
+                return Err(anyhow!("需要 http(s)://host 形式的 URL: {}", address));
+            }
+        }
+        ProbeKind::Dns => {
+            if address.is_empty() || address.contains(|c: char| c.is_whitespace() || c == '/') {
+                return Err(anyhow!("无效域名: {}", address));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 解析 `host:port` 形式的地址，取第一个结果
+///
+/// 每轮探测前调用一次，使主机名目标能跟随 DNS 变化。
+pub(crate) async fn resolve(addr: &str) -> Result<SocketAddr> {
+    tokio::net::lookup_host(addr)
+        .await
+        .map_err(|e| fail(ErrorKind::Dns, format!("解析失败: {}: {}", addr, e)))?
+        .next()
+        .ok_or_else(|| fail(ErrorKind::Dns, format!("解析无结果: {}", addr)))
+}
+
+/// 共享的 rustls 客户端（webpki 根证书，ring 加密后端）
+pub(crate) fn tls_connector() -> TlsConnector {
+    static CONNECTOR: Lazy<TlsConnector> = Lazy::new(|| {
+        let roots = RootCertStore::from_iter(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+        let config = ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .expect("ring 支持默认 TLS 版本")
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        TlsConnector::from(Arc::new(config))
+    });
+    CONNECTOR.clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 按顺序构造一轮探测，`None` 为失败
+    fn round(rtts: &[Option<f64>]) -> ProbeResult {
+        ProbeResult {
+            attempts: rtts
+                .iter()
+                .map(|&rtt| Attempt {
+                    at: Utc::now(),
+                    rtt,
+                    error: rtt.is_none().then(|| Failure {
+                        kind: ErrorKind::Timeout,
+                        message: String::from("超时"),
+                    }),
+                    timings: PhaseTimings::default(),
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn close(a: Option<f64>, b: f64) -> bool {
+        a.is_some_and(|a| (a - b).abs() < 1e-9)
+    }
+
+    #[test]
+    fn stats_without_samples() {
+        let stats = round(&[]).stats();
+        assert_eq!(stats.loss, 0.0);
+        assert!(stats.avg.is_none() && stats.p95.is_none() && stats.jitter.is_none());
+
+        let stats = round(&[None, None]).stats();
+        assert_eq!(stats.loss, 100.0);
+        assert!(stats.min.is_none() && stats.max.is_none() && stats.stddev.is_none());
+    }
+
+    #[test]
+    fn stats_with_one_sample() {
+        let stats = round(&[None, Some(42.0)]).stats();
+        assert_eq!(stats.loss, 50.0);
+        for value in [stats.avg, stats.min, stats.max, stats.p50, stats.p95] {
+            assert!(close(value, 42.0));
+        }
+        assert!(close(stats.stddev, 0.0));
+        assert!(close(stats.jitter, 0.0));
+    }
+
+    #[test]
+    fn stats_with_several_samples() {
+        let stats = round(&[Some(10.0), Some(30.0), None, Some(20.0), Some(40.0)]).stats();
+        assert_eq!(stats.loss, 20.0);
+        assert!(close(stats.avg, 25.0));
+        assert!(close(stats.min, 10.0));
+        assert!(close(stats.max, 40.0));
+        assert!(close(stats.p50, 20.0));
+        assert!(close(stats.p95, 40.0));
+        assert!(close(stats.stddev, 125f64.sqrt()));
+        // 按探测顺序：|30-10| + |20-30| + |40-20|，失败的探测不参与
+        assert!(close(stats.jitter, 50.0 / 3.0));
+    }
+
+    #[test]
+    fn percentile_uses_nearest_rank() {
+        let sorted: Vec<f64> = (1..=20).map(f64::from).collect();
+        assert_eq!(percentile(&sorted, 0.5), 10.0);
+        assert_eq!(percentile(&sorted, 0.95), 19.0);
+        assert_eq!(percentile(&sorted, 0.0), 1.0);
+        assert_eq!(percentile(&sorted, 1.0), 20.0);
+    }
+}
@@ -0,0 +1,55 @@
+// src/probe/tcp.rs
+use super::{
+    elapsed_ms, fail, failed_round, resolve, run_attempts, ErrorKind, ProbeResult, Prober, Sample,
+};
+use crate::config::TargetConfig;
+use anyhow::Result;
+use futures::future::BoxFuture;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+
+/// 默认 TCP 建连超时时间
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// TCP 建连探测器，每轮开始时重新解析一次地址
+pub struct TcpProber {
+    alias: String,
+    address: String,
+    timeout: Duration,
+}
+
+impl TcpProber {
+    pub fn new(target: &TargetConfig, timeout: Duration) -> Self {
+        TcpProber {
+            alias: target.alias.clone(),
+            address: target.address.clone(),
+            timeout,
+        }
+    }
+}
+
+impl Prober for TcpProber {
+    fn probe(&self, attempts: usize) -> BoxFuture<'_, ProbeResult> {
+        Box::pin(async move {
+            let sock = match resolve(&self.address).await {
+                Ok(sock) => sock,
+                Err(e) => return failed_round(&self.alias, attempts, &e),
+            };
+            let timeout = self.timeout;
+            run_attempts(&self.alias, attempts, move || async move {
+                Ok(Sample::rtt(probe_tcp(sock, timeout).await?))
+            })
+            .await
+        })
+    }
+}
+
+/// 对已解析的地址做一次 TCP 建连，返回耗时 (ms)
+pub async fn probe_tcp(sock: SocketAddr, timeout: Duration) -> Result<f64> {
+    let start = Instant::now();
+    tokio::time::timeout(timeout, TcpStream::connect(sock))
+        .await
+        .map_err(|_| fail(ErrorKind::Timeout, "超时"))??;
+    Ok(elapsed_ms(start))
+}
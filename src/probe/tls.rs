@@ -0,0 +1,97 @@
+// src/probe/tls.rs
+use super::{
+    elapsed_ms, fail, run_attempts, split_host_port, tls_connector, ErrorKind, PhaseTimings,
+    ProbeResult, Prober, Sample,
+};
+use crate::config::TargetConfig;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+use tokio_rustls::rustls::pki_types::ServerName;
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+/// 默认 TLS 探测的超时时间（含建连与握手）
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 一次成功的 TLS 握手
+#[derive(Debug, Clone, Copy)]
+pub struct TlsOutcome {
+    /// 握手耗时 (ms)
+    pub handshake: f64,
+    pub timings: PhaseTimings,
+    /// 对端叶子证书的 notAfter
+    pub not_after: Option<DateTime<Utc>>,
+}
+
+/// TLS 握手探测器
+pub struct TlsProber {
+    target: TargetConfig,
+    timeout: Duration,
+}
+
+impl TlsProber {
+    pub fn new(target: &TargetConfig, timeout: Duration) -> Self {
+        TlsProber {
+            target: target.clone(),
+            timeout,
+        }
+    }
+}
+
+impl Prober for TlsProber {
+    fn probe(&self, attempts: usize) -> BoxFuture<'_, ProbeResult> {
+        let (target, timeout) = (&self.target, self.timeout);
+        Box::pin(run_attempts(&target.alias, attempts, move || async move {
+            let out = probe_tls(target, timeout).await?;
+            Ok(Sample {
+                rtt: out.handshake,
+                timings: out.timings,
+                not_after: out.not_after,
+                ..Default::default()
+            })
+        }))
+    }
+}
+
+/// 对 `target.address` (host:port) 做一次完整的 TLS 握手
+///
+/// 证书校验失败（含已过期）视为探测失败。
+pub async fn probe_tls(target: &TargetConfig, timeout: Duration) -> Result<TlsOutcome> {
+    tokio::time::timeout(timeout, probe_inner(target))
+        .await
+        .map_err(|_| fail(ErrorKind::Timeout, "超时"))?
+}
+
+async fn probe_inner(target: &TargetConfig) -> Result<TlsOutcome> {
+    let (host, port) = split_host_port(&target.address)?;
+    let sni = target.sni.clone().unwrap_or_else(|| host.clone());
+    let name = ServerName::try_from(sni)?;
+
+    let start = Instant::now();
+    let stream = TcpStream::connect((host.as_str(), port)).await?;
+    let connect = elapsed_ms(start);
+
+    let start = Instant::now();
+    let tls = tls_connector().connect(name, stream).await?;
+    let handshake = elapsed_ms(start);
+
+    let not_after = tls
+        .get_ref()
+        .1
+        .peer_certificates()
+        .and_then(|certs| certs.first())
+        .and_then(|der| X509Certificate::from_der(der.as_ref()).ok())
+        .and_then(|(_, cert)| DateTime::from_timestamp(cert.validity().not_after.timestamp(), 0));
+
+    Ok(TlsOutcome {
+        handshake,
+        timings: PhaseTimings {
+            connect: Some(connect),
+            tls: Some(handshake),
+            ..Default::default()
+        },
+        not_after,
+    })
+}
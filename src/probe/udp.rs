@@ -0,0 +1,125 @@
+// src/probe/udp.rs
+use super::{
+    elapsed_ms, fail, failed_round, resolve, run_attempts, ErrorKind, ProbeResult, Prober, Sample,
+};
+use crate::config::TargetConfig;
+use anyhow::{anyhow, bail, Context, Result};
+use futures::future::BoxFuture;
+use regex::bytes::Regex;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
+
+/// 默认等待 UDP 应答的超时时间
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// UDP 请求/应答探测器，每轮开始时重新解析一次地址
+pub struct UdpProber {
+    target: TargetConfig,
+    /// 解码好的负载
+    payload: Vec<u8>,
+    /// 编译好的 `expect`
+    expect: Option<Regex>,
+    timeout: Duration,
+}
+
+impl UdpProber {
+    /// `payload_hex` 或 `expect` 无效时返回错误
+    pub fn new(target: &TargetConfig, timeout: Duration) -> Result<Self> {
+        let payload = payload(target).context("payload_hex 无效")?;
+        let expect = target
+            .expect
+            .as_deref()
+            .map(Regex::new)
+            .transpose()
+            .context("expect 无效")?;
+        Ok(UdpProber {
+            target: target.clone(),
+            payload,
+            expect,
+            timeout,
+        })
+    }
+}
+
+impl Prober for UdpProber {
+    fn probe(&self, attempts: usize) -> BoxFuture<'_, ProbeResult> {
+        Box::pin(async move {
+            let (t, timeout) = (&self.target, self.timeout);
+            let sock = match resolve(&t.address).await {
+                Ok(sock) => sock,
+                Err(e) => return failed_round(&t.alias, attempts, &e),
+            };
+            let (payload, expect) = (&self.payload, self.expect.as_ref());
+            run_attempts(&t.alias, attempts, move || async move {
+                Ok(Sample::rtt(probe_udp(sock, payload, expect, timeout).await?))
+            })
+            .await
+        })
+    }
+}
+
+/// 向已解析的地址发送一次 `payload`，等待应答，返回往返耗时 (ms)
+///
+/// 给出 `expect` 时只有匹配该正则（按字节匹配）的应答才算成功，
+/// 其余应答会被忽略并继续等待直到超时。
+pub async fn probe_udp(
+    sock: SocketAddr,
+    payload: &[u8],
+    expect: Option<&Regex>,
+    timeout: Duration,
+) -> Result<f64> {
+    let bind: SocketAddr = if sock.is_ipv4() {
+        (Ipv4Addr::UNSPECIFIED, 0).into()
+    } else {
+        (Ipv6Addr::UNSPECIFIED, 0).into()
+    };
+    let socket = UdpSocket::bind(bind).await?;
+    socket.connect(sock).await?;
+
+    let start = Instant::now();
+    socket.send(payload).await?;
+    let mut buf = vec![0u8; 65535];
+    tokio::time::timeout(timeout, async {
+        loop {
+            let n = socket.recv(&mut buf).await?;
+            match expect {
+                Some(re) if !re.is_match(&buf[..n]) => continue,
+                _ => return Ok(elapsed_ms(start)),
+            }
+        }
+    })
+    .await
+    .map_err(|_| {
+        let what = if expect.is_some() {
+            "未收到匹配的应答"
+        } else {
+            "未收到应答"
+        };
+        fail(ErrorKind::Timeout, format!("超时，{}", what))
+    })?
+}
+
+/// 取出要发送的负载：`payload_hex` 优先，其次 `payload` 文本，都没有则发送空包
+fn payload(target: &TargetConfig) -> Result<Vec<u8>> {
+    match (&target.payload_hex, &target.payload) {
+        (Some(hex), _) => decode_hex(hex),
+        (None, Some(text)) => Ok(text.as_bytes().to_vec()),
+        (None, None) => Ok(Vec::new()),
+    }
+}
+
+/// 解析十六进制字符串，允许中间夹杂空白
+fn decode_hex(s: &str) -> Result<Vec<u8>> {
+    let digits: Vec<u8> = s.bytes().filter(|b| !b.is_ascii_whitespace()).collect();
+    if !digits.len().is_multiple_of(2) {
+        bail!("十六进制负载长度必须为偶数: {}", s);
+    }
+    digits
+        .chunks(2)
+        .map(|pair| {
+            let pair = std::str::from_utf8(pair)?;
+            u8::from_str_radix(pair, 16).map_err(|_| anyhow!("无效十六进制: {}", pair))
+        })
+        .collect()
+}
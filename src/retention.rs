@@ -0,0 +1,89 @@
+// src/retention.rs
+use crate::config::Config;
+use crate::db::{Db, Resolution};
+use crate::targets::Targets;
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use log::{debug, error, info};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// 压缩任务的运行间隔
+const COMPACT_EVERY: std::time::Duration = std::time::Duration::from_secs(300);
+/// 每次汇总的最长时间段，避免长时间占用数据库
+const CHUNK: Duration = Duration::days(1);
+
+/// 后台压缩：把原始数据汇总为 5 分钟与 1 小时的桶，并按保留策略删除过期数据
+pub fn spawn_compaction(cfg: Config, db: Arc<Db>, targets: Targets) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(COMPACT_EVERY);
+        loop {
+            ticker.tick().await;
+            if let Err(e) = compact(&cfg, &db, &targets).await {
+                error!("压缩 metrics 失败: {}", e);
+            }
+        }
+    });
+}
+
+async fn compact(cfg: &Config, db: &Db, targets: &Targets) -> Result<()> {
+    let now = Utc::now();
+    let thresholds: HashMap<String, f64> = targets
+        .snapshot()
+        .iter()
+        .map(|t| (t.alias.clone(), t.loss_threshold()))
+        .collect();
+
+    // 1. 汇总已经结束的桶；多留一个桶的余量，等待仍在探测中的轮次入库
+    let mut rolled_up = now;
+    for resolution in [Resolution::FiveMinutes, Resolution::Hourly] {
+        let (Some(step), Some(key)) = (resolution.step(), resolution.state_key()) else {
+            continue;
+        };
+        let to = floor(now - step, step);
+        let from = match db.get_state(key).await? {
+            Some(v) => DateTime::parse_from_rfc3339(&v)?.with_timezone(&Utc),
+            None => match db.oldest(Resolution::Raw).await? {
+                Some(oldest) => floor(oldest, step),
+                None => continue,
+            },
+        };
+        let mut cursor = from;
+        while cursor < to {
+            let next = (cursor + CHUNK).min(to);
+            let buckets = db.rollup(resolution, cursor, next, thresholds.clone()).await?;
+            db.set_state(key, &next.to_rfc3339()).await?;
+            debug!("汇总 {:?} {} ~ {}: {} 个桶", resolution, cursor, next, buckets);
+            cursor = next;
+        }
+        rolled_up = rolled_up.min(cursor);
+    }
+
+    // 2. 删除过期数据；原始数据只删除已汇总的部分
+    let retention = cfg.retention();
+    let keep = [
+        (Resolution::Raw, retention.raw()),
+        (Resolution::FiveMinutes, retention.rollup_5m()),
+        (Resolution::Hourly, retention.rollup_1h()),
+    ];
+    for (resolution, keep) in keep {
+        // 超出时间范围等同于永久保留
+        let Some(mut before) = keep.and_then(|keep| now.checked_sub_signed(keep)) else {
+            continue;
+        };
+        if resolution == Resolution::Raw {
+            before = before.min(rolled_up);
+        }
+        let removed = db.prune(resolution, before).await?;
+        if removed > 0 {
+            info!("删除 {} 条过期的 {:?} 数据", removed, resolution);
+        }
+    }
+    Ok(())
+}
+
+/// 向下对齐到 `step` 的整数倍（UTC）
+fn floor(ts: DateTime<Utc>, step: Duration) -> DateTime<Utc> {
+    let step = step.num_seconds();
+    DateTime::from_timestamp(ts.timestamp().div_euclid(step) * step, 0).unwrap_or(ts)
+}
@@ -0,0 +1,455 @@
+// src/sla.rs
+use crate::alert::{format_duration, parse_duration};
+use crate::config::{Config, TargetConfig};
+use crate::db::{Db, MetricPoint, MetricQuery, Resolution, Rollup};
+use crate::targets::Targets;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, TimeZone, Utc};
+use log::{error, info};
+use std::collections::HashMap;
+use std::sync::Arc;
+use teloxide::prelude::Requester;
+use teloxide::types::ChatId;
+use teloxide::Bot;
+
+/// 已推送月报的月份（YYYY-MM），保存在 `bot_state` 中
+const REPORT_STATE_KEY: &str = "sla_report_month";
+/// 时长形式的统计区间上限
+const MAX_PERIOD_DAYS: i64 = 366;
+
+/// 某个目标在一段时间内的可用性统计
+#[derive(Debug, Clone)]
+pub struct Sla {
+    pub alias: String,
+    /// 有数据覆盖的时长（不含维护窗口）
+    pub observed: Duration,
+    /// 其中不可用的时长
+    pub downtime: Duration,
+    /// 故障次数（连续不可用的轮次算一次）
+    pub outages: usize,
+}
+
+impl Sla {
+    /// 可用率 (%)，没有数据时为 `None`
+    pub fn availability(&self) -> Option<f64> {
+        let observed = self.observed.num_milliseconds();
+        (observed > 0)
+            .then(|| 100.0 * (1.0 - self.downtime.num_milliseconds() as f64 / observed as f64))
+    }
+
+    /// 平均恢复时间
+    pub fn mttr(&self) -> Option<Duration> {
+        (self.outages > 0).then(|| self.downtime / self.outages as i32)
+    }
+
+    /// 平均故障间隔（可用时长 / 故障次数）
+    pub fn mtbf(&self) -> Option<Duration> {
+        (self.outages > 0).then(|| (self.observed - self.downtime) / self.outages as i32)
+    }
+}
+
+/// 由一段时间内的 metrics 计算目标的可用性
+///
+/// 每轮代表到下一轮为止的时长，但最多计两个探测间隔，
+/// 机器人离线造成的空档不计入；维护中的轮次既不计时长也不打断故障。
+/// 丢包率达到目标的 `loss_threshold` 视为不可用。
+pub fn compute(
+    target: &TargetConfig,
+    cfg: &Config,
+    rows: &[&MetricPoint],
+    until: DateTime<Utc>,
+) -> Sla {
+    let max_step = Duration::from_std(target.interval(cfg) * 2).unwrap_or(Duration::minutes(2));
+    let mut sla = Sla {
+        alias: target.alias.clone(),
+        observed: Duration::zero(),
+        downtime: Duration::zero(),
+        outages: 0,
+    };
+    let mut in_outage = false;
+    for (i, row) in rows.iter().enumerate() {
+        if row.maintenance() {
+            continue;
+        }
+        let next = rows.get(i + 1).map_or(until, |r| r.ts);
+        let step = (next - row.ts).clamp(Duration::zero(), max_step);
+        sla.observed += step;
+        if row.loss >= target.loss_threshold() {
+            sla.downtime += step;
+            if !in_outage {
+                sla.outages += 1;
+            }
+            in_outage = true;
+        } else {
+            in_outage = false;
+        }
+    }
+    sla
+}
+
+/// 由汇总桶计算目标的可用性，用于原始数据已被清理的区间
+///
+/// 每个桶按非维护轮数乘以探测间隔计时（不超过桶长），不可用时长按不可用轮数计算；
+/// 相邻的桶都有不可用轮次时算作同一次故障。
+pub fn compute_rollups(target: &TargetConfig, cfg: &Config, rollups: &[&Rollup], step: Duration) -> Sla {
+    let interval = Duration::from_std(target.interval(cfg)).unwrap_or(Duration::minutes(1));
+    let mut sla = Sla {
+        alias: target.alias.clone(),
+        observed: Duration::zero(),
+        downtime: Duration::zero(),
+        outages: 0,
+    };
+    let mut last_down: Option<DateTime<Utc>> = None;
+    for bucket in rollups {
+        let rounds = bucket.rounds.saturating_sub(bucket.maintenance_rounds);
+        if rounds == 0 {
+            continue;
+        }
+        let observed = (interval * rounds as i32).min(step);
+        sla.observed += observed;
+        if bucket.down_rounds > 0 {
+            sla.downtime += (interval * bucket.down_rounds as i32).min(observed);
+            if last_down != Some(bucket.ts - step) {
+                sla.outages += 1;
+            }
+            last_down = Some(bucket.ts);
+        }
+    }
+    sla
+}
+
+/// 计算一组目标在 [since, until) 内的可用性
+///
+/// 优先使用原始数据；已被清理时改用覆盖该区间的最细汇总表，
+/// 尚未汇总的最近一段仍取原始数据。
+pub async fn compute_all(
+    db: &Db,
+    cfg: &Config,
+    targets: &[TargetConfig],
+    since: DateTime<Utc>,
+    until: DateTime<Utc>,
+) -> Result<Vec<Sla>> {
+    let resolution = db.finest_resolution(since).await?;
+    let (Some(step), Some(key)) = (resolution.step(), resolution.state_key()) else {
+        return compute_raw(db, cfg, targets, since, until).await;
+    };
+    let rolled_up = match db.get_state(key).await? {
+        Some(v) => DateTime::parse_from_rfc3339(&v)?.with_timezone(&Utc).clamp(since, until),
+        None => until,
+    };
+    let rollups = db.query_rollups(resolution, since, rolled_up).await?;
+    let mut by_alias: HashMap<&str, Vec<&Rollup>> = HashMap::new();
+    for bucket in &rollups {
+        by_alias.entry(bucket.alias.as_str()).or_default().push(bucket);
+    }
+    let recent = compute_raw(db, cfg, targets, rolled_up, until).await?;
+    Ok(targets
+        .iter()
+        .zip(recent)
+        .map(|(t, recent)| {
+            let rollups = by_alias
+                .get(t.alias.as_str())
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+            let mut sla = compute_rollups(t, cfg, rollups, step);
+            sla.observed += recent.observed;
+            sla.downtime += recent.downtime;
+            sla.outages += recent.outages;
+            sla
+        })
+        .collect())
+}
+
+/// 由原始数据计算一组目标在 [since, until) 内的可用性
+async fn compute_raw(
+    db: &Db,
+    cfg: &Config,
+    targets: &[TargetConfig],
+    since: DateTime<Utc>,
+    until: DateTime<Utc>,
+) -> Result<Vec<Sla>> {
+    let query = MetricQuery::new(since, until)
+        .aliases(targets.iter().map(|t| t.alias.as_str()))
+        .resolution(Resolution::Raw);
+    let rows = db.query(query).await?;
+    let mut by_alias: HashMap<&str, Vec<&MetricPoint>> = HashMap::new();
+    for row in &rows {
+        by_alias.entry(row.alias.as_str()).or_default().push(row);
+    }
+    let until = until.min(Utc::now());
+    Ok(targets
+        .iter()
+        .map(|t| {
+            let rows = by_alias
+                .get(t.alias.as_str())
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+            compute(t, cfg, rows, until)
+        })
+        .collect())
+}
+
+/// 本地时区某月 1 日 0 点
+fn month_start(year: i32, month: u32) -> Result<DateTime<Utc>> {
+    let date = NaiveDate::from_ymd_opt(year, month, 1).ok_or_else(|| anyhow!("无效月份"))?;
+    Local
+        .from_local_datetime(&date.and_hms_opt(0, 0, 0).unwrap_or_default())
+        .earliest()
+        .map(|d| d.with_timezone(&Utc))
+        .ok_or_else(|| anyhow!("无效月份"))
+}
+
+/// 上个月的起止时间及 YYYY-MM 标识
+fn last_month(now: DateTime<Utc>) -> Result<(DateTime<Utc>, DateTime<Utc>, String)> {
+    let local = now.with_timezone(&Local);
+    let (year, month) = if local.month() == 1 {
+        (local.year() - 1, 12)
+    } else {
+        (local.year(), local.month() - 1)
+    };
+    let start = month_start(year, month)?;
+    let end = month_start(local.year(), local.month())?;
+    Ok((start, end, format!("{:04}-{:02}", year, month)))
+}
+
+/// 解析统计区间：`7d`/`30d` 等时长、`month`（本月至今）或 `lastmonth`
+pub fn parse_period(s: &str, now: DateTime<Utc>) -> Result<(DateTime<Utc>, DateTime<Utc>, String)> {
+    match s.to_ascii_lowercase().as_str() {
+        "month" => {
+            let local = now.with_timezone(&Local);
+            let start = month_start(local.year(), local.month())?;
+            Ok((start, now, format!("{} 月至今", local.format("%Y-%m"))))
+        }
+        "lastmonth" => {
+            let (start, end, label) = last_month(now)?;
+            Ok((start, end, format!("{} 月", label)))
+        }
+        _ => {
+            let d = parse_duration(s)
+                .map_err(|_| anyhow!("无效区间: {}（可选 7d、30d、month、lastmonth）", s))?;
+            if d > Duration::days(MAX_PERIOD_DAYS) {
+                return Err(anyhow!("统计区间需在 {}d 以内: {}", MAX_PERIOD_DAYS, s));
+            }
+            let since = now
+                .checked_sub_signed(d)
+                .ok_or_else(|| anyhow!("无效区间: {}", s))?;
+            Ok((since, now, format!("过去 {}", format_duration(d))))
+        }
+    }
+}
+
+/// 把统计结果格式化为消息文本
+pub fn format_report(title: &str, reports: &[Sla]) -> String {
+    let mut text = format!("📊 {}\n", title);
+    for sla in reports {
+        let Some(availability) = sla.availability() else {
+            text.push_str(&format!("{}: 暂无数据\n", sla.alias));
+            continue;
+        };
+        text.push_str(&format!("{}: 可用率 {:.3}%\n", sla.alias, availability));
+        text.push_str(&format!(
+            "    停机 {}，故障 {} 次",
+            format_duration(sla.downtime),
+            sla.outages
+        ));
+        if let (Some(mttr), Some(mtbf)) = (sla.mttr(), sla.mtbf()) {
+            text.push_str(&format!(
+                "，MTTR {}，MTBF {}",
+                format_duration(mttr),
+                format_duration(mtbf)
+            ));
+        }
+        text.push('\n');
+    }
+    text
+}
+
+/// 每月初向订阅会话推送上个月的可用性月报，已推送的月份记录在数据库中
+pub fn spawn_monthly_report(cfg: Config, db: Arc<Db>, targets: Targets, bot: Bot) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(std::time::Duration::from_secs(3600));
+        loop {
+            ticker.tick().await;
+            if let Err(e) = post_monthly_report(&cfg, &db, &targets, &bot).await {
+                error!("推送月报失败: {}", e);
+            }
+        }
+    });
+}
+
+async fn post_monthly_report(cfg: &Config, db: &Db, targets: &Targets, bot: &Bot) -> Result<()> {
+    let (start, end, month) = last_month(Utc::now())?;
+    if db.get_state(REPORT_STATE_KEY).await?.as_deref() == Some(month.as_str()) {
+        return Ok(());
+    }
+    info!("生成 {} 月报", month);
+    let reports = compute_all(db, cfg, &targets.snapshot(), start, end).await?;
+    for chat_id in db.list_subscriptions().await? {
+        let visible = targets.visible_to(db, chat_id).await?;
+        let chat_reports: Vec<Sla> = reports
+            .iter()
+            .filter(|r| r.availability().is_some() && visible.iter().any(|t| t.alias == r.alias))
+            .cloned()
+            .collect();
+        if chat_reports.is_empty() {
+            continue;
+        }
+        let text = format_report(&format!("{} 月可用性报告", month), &chat_reports);
+        if let Err(e) = bot.send_message(ChatId(chat_id), text).await {
+            error!("推送月报失败 [{}]: {}", chat_id, e);
+        }
+    }
+    db.set_state(REPORT_STATE_KEY, &month).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup() -> (TargetConfig, Config) {
+        let cfg: Config = toml::from_str("token = \"\"\nadmins = []\ntargets = []").unwrap();
+        let target = TargetConfig::new("hk", "127.0.0.1:22", crate::config::ProbeKind::Tcp);
+        (target, cfg)
+    }
+
+    fn base() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 10, 1, 0, 0, 0).unwrap()
+    }
+
+    /// 每分钟一轮：(丢包率, 是否维护)
+    fn points(rounds: &[(f64, bool)]) -> Vec<MetricPoint> {
+        rounds
+            .iter()
+            .enumerate()
+            .map(|(i, &(loss, maintenance))| MetricPoint {
+                alias: String::from("hk"),
+                ts: base() + Duration::minutes(i as i64),
+                rounds: 1,
+                maintenance_rounds: maintenance as u32,
+                latency: (loss < 100.0).then_some(10.0),
+                p95: None,
+                loss,
+            })
+            .collect()
+    }
+
+    fn run(rounds: &[(f64, bool)]) -> Sla {
+        let (target, cfg) = setup();
+        let rows = points(rounds);
+        let refs: Vec<&MetricPoint> = rows.iter().collect();
+        let until = base() + Duration::minutes(rounds.len() as i64);
+        compute(&target, &cfg, &refs, until)
+    }
+
+    #[test]
+    fn no_incidents() {
+        let sla = run(&[(0.0, false); 10]);
+        assert_eq!(sla.observed, Duration::minutes(10));
+        assert_eq!(sla.downtime, Duration::zero());
+        assert_eq!(sla.outages, 0);
+        assert_eq!(sla.availability(), Some(100.0));
+        assert_eq!(sla.mttr(), None);
+        assert_eq!(sla.mtbf(), None);
+    }
+
+    #[test]
+    fn no_data() {
+        let sla = run(&[]);
+        assert_eq!(sla.availability(), None);
+        assert_eq!(sla.outages, 0);
+    }
+
+    #[test]
+    fn uptime_mttr_and_mtbf() {
+        let mut rounds = [(0.0, false); 10];
+        rounds[2] = (100.0, false);
+        rounds[3] = (100.0, false);
+        rounds[7] = (100.0, false);
+        let sla = run(&rounds);
+        assert_eq!(sla.observed, Duration::minutes(10));
+        assert_eq!(sla.downtime, Duration::minutes(3));
+        assert_eq!(sla.outages, 2);
+        assert!((sla.availability().unwrap() - 70.0).abs() < 1e-9);
+        assert_eq!(sla.mttr(), Some(Duration::seconds(90)));
+        assert_eq!(sla.mtbf(), Some(Duration::seconds(210)));
+    }
+
+    #[test]
+    fn maintenance_rounds_are_excluded() {
+        // 维护中的离线轮次不计入，也不打断前后的同一次故障
+        let rounds = [
+            (0.0, false),
+            (100.0, false),
+            (100.0, true),
+            (100.0, true),
+            (100.0, false),
+            (0.0, false),
+        ];
+        let sla = run(&rounds);
+        assert_eq!(sla.observed, Duration::minutes(4));
+        assert_eq!(sla.downtime, Duration::minutes(2));
+        assert_eq!(sla.outages, 1);
+        assert!((sla.availability().unwrap() - 50.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn gaps_count_at_most_two_intervals() {
+        let (target, cfg) = setup();
+        let mut rows = points(&[(0.0, false), (0.0, false)]);
+        rows[1].ts = base() + Duration::minutes(30);
+        let refs: Vec<&MetricPoint> = rows.iter().collect();
+        let sla = compute(&target, &cfg, &refs, base() + Duration::minutes(31));
+        assert_eq!(sla.observed, Duration::minutes(3));
+    }
+
+    fn rollup(bucket: i64, rounds: u32, down_rounds: u32, maintenance_rounds: u32) -> Rollup {
+        Rollup {
+            alias: String::from("hk"),
+            ts: base() + Duration::minutes(5 * bucket),
+            rounds,
+            down_rounds,
+            maintenance_rounds,
+        }
+    }
+
+    #[test]
+    fn rollups_count_rounds_and_merge_adjacent_outages() {
+        let (target, cfg) = setup();
+        let buckets = [
+            rollup(0, 5, 0, 0),
+            rollup(1, 5, 2, 0),
+            rollup(2, 5, 5, 0),
+            rollup(3, 5, 0, 0),
+            rollup(5, 5, 1, 0),
+        ];
+        let refs: Vec<&Rollup> = buckets.iter().collect();
+        let sla = compute_rollups(&target, &cfg, &refs, Duration::minutes(5));
+        assert_eq!(sla.observed, Duration::minutes(25));
+        assert_eq!(sla.downtime, Duration::minutes(8));
+        assert_eq!(sla.outages, 2);
+    }
+
+    #[test]
+    fn rollups_exclude_maintenance_rounds() {
+        let (target, cfg) = setup();
+        let buckets = [rollup(0, 5, 0, 5), rollup(1, 5, 1, 3), rollup(2, 5, 0, 0)];
+        let refs: Vec<&Rollup> = buckets.iter().collect();
+        let sla = compute_rollups(&target, &cfg, &refs, Duration::minutes(5));
+        assert_eq!(sla.observed, Duration::minutes(7));
+        assert_eq!(sla.downtime, Duration::minutes(1));
+        assert_eq!(sla.outages, 1);
+
+        let empty = compute_rollups(&target, &cfg, &[], Duration::minutes(5));
+        assert_eq!(empty.availability(), None);
+        assert_eq!(empty.mttr(), None);
+    }
+
+    #[test]
+    fn period_is_capped() {
+        let now = base();
+        assert!(parse_period("30d", now).is_ok());
+        assert!(parse_period("367d", now).is_err());
+        assert!(parse_period("99999999w", now).is_err());
+    }
+}
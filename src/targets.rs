@@ -0,0 +1,89 @@
+// src/targets.rs
+use crate::config::TargetConfig;
+use crate::db::Db;
+use std::sync::Arc;
+use tokio::sync::watch;
+
+/// 运行期的监测目标列表
+///
+/// 由 `config.toml` 中的目标与通过 `/add` 添加的目标组成，
+/// 后台监测订阅其变化，增删目标无需重启。
+#[derive(Clone)]
+pub struct Targets {
+    tx: Arc<watch::Sender<Vec<TargetConfig>>>,
+    /// 来自配置文件的别名，不能通过命令删除
+    from_config: Arc<Vec<String>>,
+}
+
+impl Targets {
+    /// `configured` 优先，`added` 中与其别名重复的目标会被忽略
+    pub fn new(configured: Vec<TargetConfig>, added: Vec<TargetConfig>) -> Self {
+        let from_config: Vec<String> = configured.iter().map(|t| t.alias.clone()).collect();
+        let mut list = configured;
+        for t in added {
+            if list.iter().any(|c| c.alias == t.alias) {
+                log::warn!("目标 [{}] 与 config.toml 中的别名重复，已忽略", t.alias);
+            } else {
+                list.push(t);
+            }
+        }
+        let (tx, _) = watch::channel(list);
+        Targets {
+            tx: Arc::new(tx),
+            from_config: Arc::new(from_config),
+        }
+    }
+
+    /// 当前所有目标的快照
+    pub fn snapshot(&self) -> Vec<TargetConfig> {
+        self.tx.borrow().clone()
+    }
+
+    /// 会话可见的目标：未分配给任何会话的目标对所有会话可见
+    pub async fn visible_to(&self, db: &Db, chat_id: i64) -> rusqlite::Result<Vec<TargetConfig>> {
+        let assignments = db.list_target_chats().await?;
+        Ok(self
+            .snapshot()
+            .into_iter()
+            .filter(|t| {
+                let assigned = assignments.get(&t.alias).map(Vec::as_slice).unwrap_or_default();
+                t.visible_to(chat_id, assigned)
+            })
+            .collect())
+    }
+
+    /// 订阅目标列表的变化
+    pub fn subscribe(&self) -> watch::Receiver<Vec<TargetConfig>> {
+        self.tx.subscribe()
+    }
+
+    /// 是否为配置文件中的目标
+    pub fn is_configured(&self, alias: &str) -> bool {
+        self.from_config.iter().any(|a| a == alias)
+    }
+
+    /// 是否已存在该别名
+    pub fn contains(&self, alias: &str) -> bool {
+        self.tx.borrow().iter().any(|t| t.alias == alias)
+    }
+
+    /// 添加目标，别名已存在时返回 false
+    pub fn add(&self, target: TargetConfig) -> bool {
+        self.tx.send_if_modified(|list| {
+            if list.iter().any(|t| t.alias == target.alias) {
+                return false;
+            }
+            list.push(target);
+            true
+        })
+    }
+
+    /// 删除目标，不存在时返回 false
+    pub fn remove(&self, alias: &str) -> bool {
+        self.tx.send_if_modified(|list| {
+            let before = list.len();
+            list.retain(|t| t.alias != alias);
+            list.len() != before
+        })
+    }
+}